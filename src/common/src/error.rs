@@ -23,6 +23,12 @@ use thiserror::Error;
 pub enum Error {
     #[error(transparent)]
     Internal(#[from] anyhow::Error),
+
+    #[error("Task panicked, context:{context}, msg:{message}")]
+    Panic { context: String, message: String },
+
+    #[error("Write batch too large, size:{size}, limit:{limit}")]
+    WriteBatchTooLarge { size: usize, limit: usize },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
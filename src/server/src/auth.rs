@@ -0,0 +1,135 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Bearer token authentication for http endpoints.
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::ErrorUnauthorized,
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    web::Data,
+    Error,
+};
+use subtle::ConstantTimeEq;
+
+/// Endpoints which can be accessed without token.
+const UNAUTHENTICATED_PATHS: [&str; 2] = ["/", "/metrics"];
+
+/// Expected token, requests are not checked when it's not registered as app
+/// data.
+pub struct BearerToken(pub String);
+
+/// Middleware rejecting requests without a matching
+/// `Authorization: Bearer <token>` header.
+pub async fn check_bearer_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(token) = req.app_data::<Data<BearerToken>>() {
+        if !UNAUTHENTICATED_PATHS.contains(&req.path()) {
+            let provided = req
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|v| v.as_bytes().strip_prefix(b"Bearer "));
+            // Compare in constant time, so the time taken doesn't reveal how
+            // many leading bytes match.
+            let matched = provided.is_some_and(|v| v.ct_eq(token.0.as_bytes()).into());
+            if !matched {
+                return Err(ErrorUnauthorized("invalid or missing bearer token"));
+            }
+        }
+    }
+
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        get,
+        http::StatusCode,
+        middleware::from_fn,
+        test::{self, TestRequest},
+        App, HttpResponse, Responder,
+    };
+
+    use super::*;
+
+    #[get("/")]
+    async fn hello() -> impl Responder {
+        HttpResponse::Ok()
+    }
+
+    #[get("/compact")]
+    async fn compact() -> impl Responder {
+        HttpResponse::Ok()
+    }
+
+    #[get("/metrics")]
+    async fn metrics() -> impl Responder {
+        HttpResponse::Ok()
+    }
+
+    #[actix_web::test]
+    async fn test_check_bearer_token() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(BearerToken("secret".to_string())))
+                .wrap(from_fn(check_bearer_token))
+                .service(hello)
+                .service(compact)
+                .service(metrics),
+        )
+        .await;
+
+        let testcases = [
+            ("/compact", None, StatusCode::UNAUTHORIZED),
+            ("/compact", Some("Bearer wrong"), StatusCode::UNAUTHORIZED),
+            ("/compact", Some("secret"), StatusCode::UNAUTHORIZED),
+            ("/compact", Some("Bearer secret"), StatusCode::OK),
+            // Allowlisted paths
+            ("/", None, StatusCode::OK),
+            ("/metrics", None, StatusCode::OK),
+        ];
+        for (path, auth, expected) in testcases {
+            let mut req = TestRequest::get().uri(path);
+            if let Some(auth) = auth {
+                req = req.insert_header((AUTHORIZATION, auth));
+            }
+            let status = match test::try_call_service(&app, req.to_request()).await {
+                Ok(resp) => resp.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+            assert_eq!(expected, status, "path:{path}, auth:{auth:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_no_token_configured() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(check_bearer_token))
+                .service(compact),
+        )
+        .await;
+
+        let resp = test::call_service(&app, TestRequest::get().uri("/compact").to_request()).await;
+        assert_eq!(StatusCode::OK, resp.status());
+    }
+}
@@ -15,6 +15,8 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::fmt;
+
 use common::ReadableDuration;
 use serde::{Deserialize, Serialize};
 
@@ -22,6 +24,15 @@ use serde::{Deserialize, Serialize};
 #[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub port: u16,
+    // Worker threads of the runtime running http server.
+    pub main_thread_num: usize,
+    pub http_worker_num: usize,
+    // Max in-flight requests of the whole server, requests beyond it are
+    // rejected with 503, `None` means no limit.
+    pub http_max_in_flight_requests: Option<usize>,
+    // Require `Authorization: Bearer <token>` for all endpoints except `/` and
+    // `/metrics`, `None` means no authentication.
+    pub auth_token: Option<AuthToken>,
     pub test: TestConfig, // for test
     pub metric_engine: MetricEngineConfig,
 }
@@ -30,12 +41,27 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             port: 5000,
+            main_thread_num: 1,
+            http_worker_num: 4,
+            http_max_in_flight_requests: None,
+            auth_token: None,
             test: TestConfig::default(),
             metric_engine: MetricEngineConfig::default(),
         }
     }
 }
 
+/// Token is redacted in debug output, since config is logged on startup.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct AuthToken(pub String);
+
+impl fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuthToken(******)")
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct TestConfig {
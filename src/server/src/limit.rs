@@ -0,0 +1,124 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Limit of in-flight http requests across all workers.
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::ErrorServiceUnavailable,
+    middleware::Next,
+    web::Data,
+    Error,
+};
+use tokio::sync::Semaphore;
+
+/// Max in-flight requests, requests are not limited when it's not registered
+/// as app data.
+///
+/// It should be created once and shared by all workers, so the limit applies
+/// to the whole server instead of each worker.
+pub struct RequestLimit(Semaphore);
+
+impl RequestLimit {
+    pub fn new(max_in_flight_requests: usize) -> Self {
+        Self(Semaphore::new(max_in_flight_requests))
+    }
+}
+
+/// Middleware rejecting requests with 503 when the limit is reached, instead
+/// of queueing them.
+pub async fn limit_in_flight_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let limit = req.app_data::<Data<RequestLimit>>().cloned();
+    let _permit = match &limit {
+        Some(limit) => Some(
+            limit
+                .0
+                .try_acquire()
+                .map_err(|_| ErrorServiceUnavailable("too many in-flight requests"))?,
+        ),
+        None => None,
+    };
+
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::{
+        get,
+        http::StatusCode,
+        middleware::from_fn,
+        test::{self, TestRequest},
+        App, HttpResponse, Responder,
+    };
+
+    use super::*;
+
+    #[get("/slow")]
+    async fn slow() -> impl Responder {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        HttpResponse::Ok()
+    }
+
+    #[actix_web::test]
+    async fn test_limit_in_flight_requests() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(RequestLimit::new(1)))
+                .wrap(from_fn(limit_in_flight_requests))
+                .service(slow),
+        )
+        .await;
+        let call = || async {
+            let req = TestRequest::get().uri("/slow").to_request();
+            match test::try_call_service(&app, req).await {
+                Ok(resp) => resp.status(),
+                Err(e) => e.as_response_error().status_code(),
+            }
+        };
+
+        let (first, second) = tokio::join!(call(), call());
+        assert_eq!(StatusCode::OK, first);
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, second);
+        // Permit is released after the request finishes.
+        assert_eq!(StatusCode::OK, call().await);
+    }
+
+    #[actix_web::test]
+    async fn test_no_limit_configured() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(limit_in_flight_requests))
+                .service(slow),
+        )
+        .await;
+
+        let req = || TestRequest::get().uri("/slow").to_request();
+        let (first, second) = tokio::join!(
+            test::call_service(&app, req()),
+            test::call_service(&app, req())
+        );
+        assert_eq!(StatusCode::OK, first.status());
+        assert_eq!(StatusCode::OK, second.status());
+    }
+}
@@ -16,7 +16,9 @@
 // under the License.
 
 #![feature(duration_constructors)]
+mod auth;
 mod config;
+mod limit;
 use std::{
     fs,
     iter::repeat_with,
@@ -29,6 +31,7 @@ use std::{
 
 use actix_web::{
     get,
+    middleware::from_fn,
     web::{self, Data},
     App, HttpResponse, HttpServer, Responder,
 };
@@ -36,14 +39,17 @@ use arrow::{
     array::{Int64Array, RecordBatch},
     datatypes::{DataType, Field, Schema, SchemaRef},
 };
+use auth::{check_bearer_token, BearerToken};
 use clap::Parser;
 use config::{Config, ObjectStorageConfig};
 use horaedb_storage::{
+    runtime::RuntimeStats,
     storage::{
         CloudObjectStorage, CompactRequest, StorageRuntimes, TimeMergeStorageRef, WriteRequest,
     },
     types::RuntimeRef,
 };
+use limit::{limit_in_flight_requests, RequestLimit};
 use object_store::local::LocalFileSystem;
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
@@ -71,17 +77,104 @@ async fn toggle(data: web::Data<AppState>) -> impl Responder {
     }
 }
 
+#[get("/toggle_compaction")]
+async fn toggle_compaction(data: web::Data<AppState>) -> impl Responder {
+    let prev = data.storage.is_compaction_enabled();
+    data.storage.set_compaction_enabled(!prev);
+    if prev {
+        HttpResponse::Ok().body("Compaction disabled!")
+    } else {
+        HttpResponse::Ok().body("Compaction enabled again!")
+    }
+}
+
 #[get("/compact")]
 async fn compact(data: web::Data<AppState>) -> impl Responder {
+    if !data.storage.is_compaction_enabled() {
+        return HttpResponse::Conflict().body("Compaction disabled, no task submitted!");
+    }
     if let Err(e) = data.storage.compact(CompactRequest::default()).await {
         println!("compact failed, err:{e}");
     }
     HttpResponse::Ok().body("Task submit!")
 }
 
+#[get("/debug/compaction_plan")]
+async fn compaction_plan(data: web::Data<AppState>) -> impl Responder {
+    match data.storage.compaction_plan().await {
+        Ok(Some(plan)) => HttpResponse::Ok().body(format!(
+            "inputs:{:?}\nexpireds:{:?}\nmax_output_size:{}",
+            plan.inputs,
+            plan.expireds,
+            plan.max_output_size()
+        )),
+        Ok(None) => HttpResponse::Ok().body("No compaction task"),
+        Err(e) => HttpResponse::InternalServerError().body(format!("get plan failed, err:{e}")),
+    }
+}
+
+#[get("/debug/orphaned_ssts")]
+async fn orphaned_ssts(data: web::Data<AppState>) -> impl Responder {
+    match data.storage.orphaned_ssts().await {
+        Ok(orphans) => {
+            let mut body = format!("Found {} orphaned ssts\n", orphans.len());
+            for sst in orphans {
+                body.push_str(&format!(
+                    "id:{}, size:{}, age:{:?}\n",
+                    sst.id, sst.size, sst.age
+                ));
+            }
+            HttpResponse::Ok().body(body)
+        }
+        Err(e) => HttpResponse::InternalServerError().body(format!("find orphans failed, err:{e}")),
+    }
+}
+
+#[get("/debug/consistency_check")]
+async fn consistency_check(data: web::Data<AppState>) -> impl Responder {
+    match data.storage.consistency_check().await {
+        Ok(report) => HttpResponse::Ok().body(format!(
+            "consistent:{}\nmissing_ssts:{:?}\norphaned_ssts:{:?}",
+            report.is_consistent(),
+            report.missing_ssts,
+            report
+                .orphaned_ssts
+                .iter()
+                .map(|sst| sst.id)
+                .collect::<Vec<_>>()
+        )),
+        Err(e) => HttpResponse::InternalServerError().body(format!("check failed, err:{e}")),
+    }
+}
+
+#[get("/metrics")]
+async fn metrics(data: web::Data<AppState>) -> impl Responder {
+    let mut body = String::new();
+    for (name, rt) in &data.runtimes {
+        let stats = RuntimeStats::new(rt);
+        body.push_str(&format!(
+            "runtime_workers{{name=\"{name}\"}} {}\nruntime_alive_tasks{{name=\"{name}\"}} {}\n",
+            stats.num_workers, stats.num_alive_tasks
+        ));
+    }
+    let stats = data.storage.stats().await;
+    body.push_str(&format!(
+        "storage_live_ssts {}\nstorage_compaction_enabled {}\n",
+        stats.num_ssts, stats.compaction_enabled as u8
+    ));
+    if let Some(requests) = stats.object_store_requests {
+        body.push_str(&format!(
+            "storage_object_store_in_flight_requests {}\nstorage_object_store_waiting_requests {}\n",
+            requests.in_flight, requests.waiting
+        ));
+    }
+    HttpResponse::Ok().body(body)
+}
+
 struct AppState {
     storage: TimeMergeStorageRef,
     keep_writing: Arc<AtomicBool>,
+    runtimes: Vec<(&'static str, RuntimeRef)>,
 }
 
 pub fn main() {
@@ -99,14 +192,27 @@ pub fn main() {
     info!("Config loaded: \n{:#?}", config);
 
     let port = config.port;
-    let rt = build_multi_runtime("main", 1);
+    let http_worker_num = config.http_worker_num;
+    // Created once so all workers share the same limit.
+    let request_limit = config.http_max_in_flight_requests.map(|v| {
+        assert!(v > 0, "http_max_in_flight_requests should be larger than 0");
+        Data::new(RequestLimit::new(v))
+    });
+    let bearer_token = config
+        .auth_token
+        .clone()
+        .map(|v| Data::new(BearerToken(v.0)));
+    let rt = build_multi_runtime("main", config.main_thread_num);
     let manifest_compact_runtime = build_multi_runtime(
         "manifest-compact",
         config.metric_engine.threads.manifest_thread_num,
     );
     let sst_compact_runtime =
         build_multi_runtime("sst-compact", config.metric_engine.threads.sst_thread_num);
-    let runtimes = StorageRuntimes::new(manifest_compact_runtime, sst_compact_runtime);
+    let runtimes = StorageRuntimes::new(
+        manifest_compact_runtime.clone(),
+        sst_compact_runtime.clone(),
+    );
     let object_store_config = match config.metric_engine.storage.object_store {
         ObjectStorageConfig::Local(v) => v,
         ObjectStorageConfig::S3Like(_) => panic!("S3 not support yet"),
@@ -118,6 +224,7 @@ pub fn main() {
     let enable_write = config.test.enable_write;
     let write_rt = build_multi_runtime("write", write_worker_num);
     let keep_writing = Arc::new(AtomicBool::new(true));
+    let main_rt = rt.clone();
     let _ = rt.block_on(async move {
         let store = Arc::new(LocalFileSystem::new());
         let storage = Arc::new(
@@ -147,16 +254,37 @@ pub fn main() {
         let app_state = Data::new(AppState {
             storage,
             keep_writing,
+            runtimes: vec![
+                ("main", main_rt),
+                ("write", write_rt),
+                ("manifest-compact", manifest_compact_runtime),
+                ("sst-compact", sst_compact_runtime),
+            ],
         });
         info!(port, "Start HoraeDB http server...");
         HttpServer::new(move || {
             App::new()
                 .app_data(app_state.clone())
+                .configure(|cfg| {
+                    if let Some(token) = &bearer_token {
+                        cfg.app_data(token.clone());
+                    }
+                    if let Some(limit) = &request_limit {
+                        cfg.app_data(limit.clone());
+                    }
+                })
+                .wrap(from_fn(check_bearer_token))
+                .wrap(from_fn(limit_in_flight_requests))
                 .service(hello)
                 .service(compact)
+                .service(compaction_plan)
                 .service(toggle)
+                .service(toggle_compaction)
+                .service(metrics)
+                .service(orphaned_ssts)
+                .service(consistency_check)
         })
-        .workers(4)
+        .workers(http_worker_num)
         .bind(("127.0.0.1", port))
         .expect("Server bind failed")
         .run()
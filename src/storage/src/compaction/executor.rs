@@ -21,7 +21,6 @@ use std::sync::{
 };
 
 use anyhow::Context;
-use async_scoped::TokioScope;
 use datafusion::{execution::TaskContext, physical_plan::execute_stream};
 use futures::StreamExt;
 use object_store::path::Path;
@@ -30,14 +29,16 @@ use parquet::{
     file::properties::WriterProperties,
 };
 use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, trace};
+use tracing::{debug, error};
 
 use crate::{
-    compaction::Task,
+    compaction::{throttle::IoThrottle, Task},
     ensure,
+    event::{EventNotifier, StorageEvent},
     manifest::{ManifestRef, ManifestUpdate},
     read::ParquetReader,
-    sst::{FileId, FileMeta, SstFile, SstPathGenerator},
+    runtime::catch_panic,
+    sst::{purge_ssts, FileMeta, SstFile, SstPathGenerator},
     types::{ObjectStoreRef, RuntimeRef, StorageSchema},
     Result,
 };
@@ -57,7 +58,9 @@ struct Inner {
     write_props: WriterProperties,
     inused_memory: AtomicU64,
     mem_limit: u64,
+    io_throttle: Option<IoThrottle>,
     trigger_tx: Sender<()>,
+    event_notifier: EventNotifier,
 }
 
 impl Executor {
@@ -71,8 +74,11 @@ impl Executor {
         parquet_reader: Arc<ParquetReader>,
         write_props: WriterProperties,
         mem_limit: u64,
+        io_rate_limit: Option<u64>,
         trigger_tx: Sender<()>,
-    ) -> Self {
+        event_notifier: EventNotifier,
+    ) -> Result<Self> {
+        let io_throttle = io_rate_limit.map(IoThrottle::try_new).transpose()?;
         let inner = Inner {
             runtime,
             store,
@@ -83,11 +89,13 @@ impl Executor {
             write_props,
             mem_limit,
             inused_memory: AtomicU64::new(0),
+            io_throttle,
             trigger_tx,
+            event_notifier,
         };
-        Self {
+        Ok(Self {
             inner: Arc::new(inner),
-        }
+        })
     }
 
     fn pre_check(&self, task: &Task) -> Result<()> {
@@ -182,11 +190,16 @@ impl Executor {
         )
         .context("create arrow writer")?;
         let mut num_rows = 0;
+        let mut io_tracker = IoTracker::new(task);
         // TODO: support multi-part write
         while let Some(batch) = stream.next().await {
             let batch = batch.context("execute plan")?;
             num_rows += batch.num_rows();
             writer.write(&batch).await.context("write batch")?;
+            if let Some(throttle) = &self.inner.io_throttle {
+                let bytes = io_tracker.advance(num_rows, writer.bytes_written());
+                throttle.acquire(bytes).await;
+            }
         }
         writer.close().await.context("close writer")?;
         let object_meta = self
@@ -195,6 +208,9 @@ impl Executor {
             .head(&file_path)
             .await
             .context("get object meta")?;
+        if let Some(throttle) = &self.inner.io_throttle {
+            throttle.acquire(io_tracker.finish(object_meta.size)).await;
+        }
         let file_meta = FileMeta {
             max_sequence: file_id,
             num_rows: num_rows as u32,
@@ -203,7 +219,8 @@ impl Executor {
         };
         debug!(file_meta = ?file_meta, "Compact output new sst");
         // First add new sst to manifest, then delete expired/old sst
-        let to_adds = vec![SstFile::new(file_id, file_meta)];
+        let output = SstFile::new(file_id, file_meta);
+        let to_adds = vec![output.clone()];
         let to_deletes = task
             .expireds
             .iter()
@@ -214,43 +231,72 @@ impl Executor {
             .manifest
             .update(ManifestUpdate::new(to_adds, to_deletes.clone()))
             .await?;
+        self.inner
+            .event_notifier
+            .notify(StorageEvent::CompactionCompleted {
+                inputs: task.inputs.iter().map(|f| f.id()).collect(),
+                expireds: task.expireds.iter().map(|f| f.id()).collect(),
+                output,
+            });
 
         // From now on, no error should be returned!
         // Because we have already updated manifest.
-        self.delete_ssts(to_deletes.into_iter());
+        purge_ssts(
+            &self.inner.store,
+            &self.inner.sst_path_gen,
+            &self.inner.event_notifier,
+            to_deletes,
+        )
+        .await;
         Ok(())
     }
+}
 
-    fn delete_ssts<I>(&self, ids: I)
-    where
-        I: Iterator<Item = FileId>,
-    {
-        let (_, results) = TokioScope::scope_and_block(|scope| {
-            for id in ids {
-                let path = Path::from(self.inner.sst_path_gen.generate(id));
-                trace!(id, "Delete sst file");
-                scope.spawn(async move {
-                    self.inner
-                        .store
-                        .delete(&path)
-                        .await
-                        .with_context(|| format!("failed to delete file, path:{path}"))
-                });
-            }
-        });
-        for res in results {
-            match res {
-                Err(e) => {
-                    error!("Failed to join delete task, err:{e}")
-                }
-                Ok(v) => {
-                    if let Err(e) = v {
-                        error!("Failed to delete sst, err:{e}")
-                    }
-                }
-            }
+/// Tracks sst bytes read and written by a compaction task, which are charged
+/// to the io throttle.
+///
+/// The reader doesn't expose how many bytes it has read, so read bytes are
+/// estimated by the rows merged so far, and the remaining input bytes are
+/// charged when the task finishes.
+struct IoTracker {
+    input_size: u64,
+    input_rows: u64,
+    read_bytes: u64,
+    written_bytes: u64,
+}
+
+impl IoTracker {
+    fn new(task: &Task) -> Self {
+        Self {
+            input_size: task.input_size(),
+            input_rows: task.inputs.iter().map(|f| f.meta().num_rows as u64).sum(),
+            read_bytes: 0,
+            written_bytes: 0,
         }
     }
+
+    /// Returns bytes read and written since last call.
+    fn advance(&mut self, merged_rows: usize, bytes_written: usize) -> u64 {
+        let read_bytes = if self.input_rows == 0 {
+            self.input_size
+        } else {
+            (self.input_size * merged_rows as u64 / self.input_rows).min(self.input_size)
+        };
+        self.charge(read_bytes, bytes_written as u64)
+    }
+
+    /// Returns bytes not charged yet, given the size of the output sst.
+    fn finish(&mut self, output_size: usize) -> u64 {
+        self.charge(self.input_size, output_size as u64)
+    }
+
+    fn charge(&mut self, read_bytes: u64, written_bytes: u64) -> u64 {
+        let bytes = read_bytes.saturating_sub(self.read_bytes)
+            + written_bytes.saturating_sub(self.written_bytes);
+        self.read_bytes = self.read_bytes.max(read_bytes);
+        self.written_bytes = self.written_bytes.max(written_bytes);
+        bytes
+    }
 }
 
 pub struct Runnable {
@@ -262,7 +308,15 @@ impl Runnable {
     fn spawn(self) {
         let rt = self.executor.inner.runtime.clone();
         rt.spawn(async move {
-            if let Err(e) = self.executor.do_compaction(&self.task).await {
+            // Ssts of a panicked task should be unmarked too, otherwise they
+            // will never be compacted again.
+            let res = catch_panic(
+                format!("compaction, inputs:{}", self.task.inputs.len()),
+                self.executor.do_compaction(&self.task),
+            )
+            .await
+            .and_then(|v| v);
+            if let Err(e) = res {
                 error!("Do compaction failed, err:{e:?}");
                 self.executor.on_failure(&self.task);
             } else {
@@ -271,3 +325,121 @@ impl Runnable {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use object_store::{local::LocalFileSystem, ObjectStore};
+    use tokio::{runtime::Runtime, sync::mpsc};
+
+    use super::*;
+    use crate::{
+        arrow_schema,
+        config::{ManifestConfig, UpdateMode},
+        manifest::Manifest,
+        record_batch,
+    };
+
+    async fn write_sst(
+        store: &ObjectStoreRef,
+        schema: &StorageSchema,
+        sst_path_gen: &SstPathGenerator,
+        num_rows: usize,
+    ) -> SstFile {
+        let file_id = SstFile::allocate_id();
+        let file_path = Path::from(sst_path_gen.generate(file_id));
+        let batch = record_batch!(
+            ("pk1", Int64, (0..num_rows as i64).collect::<Vec<_>>()),
+            ("value", Int64, vec![file_id as i64; num_rows])
+        )
+        .unwrap();
+        let batch = schema.fill_builtin_columns(batch, file_id).unwrap();
+        let mut writer = AsyncArrowWriter::try_new(
+            ParquetObjectWriter::new(store.clone(), file_path.clone()),
+            schema.arrow_schema.clone(),
+            None,
+        )
+        .unwrap();
+        writer.write(&batch).await.unwrap();
+        writer.close().await.unwrap();
+        let size = store.head(&file_path).await.unwrap().size;
+
+        SstFile::new(
+            file_id,
+            FileMeta {
+                max_sequence: file_id,
+                num_rows: num_rows as u32,
+                size: size as u32,
+                time_range: (1..10).into(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_compaction_io_throttle() {
+        let root_dir = temp_dir::TempDir::new().unwrap();
+        let runtime = Arc::new(Runtime::new().unwrap());
+        let rt = runtime.clone();
+
+        rt.block_on(async move {
+            let root_dir = root_dir.path().to_string_lossy().to_string();
+            let store: ObjectStoreRef = Arc::new(LocalFileSystem::new());
+            let schema = StorageSchema::try_new(
+                arrow_schema!(("pk1", Int64), ("value", Int64)),
+                1, // num_primary_keys
+                UpdateMode::Overwrite,
+            )
+            .unwrap();
+            let sst_path_gen = Arc::new(SstPathGenerator::new(root_dir.clone()));
+            let manifest = Arc::new(
+                Manifest::try_new(
+                    root_dir,
+                    store.clone(),
+                    runtime.clone(),
+                    ManifestConfig::default(),
+                )
+                .await
+                .unwrap(),
+            );
+            let mut inputs = Vec::new();
+            for _ in 0..2 {
+                let sst = write_sst(&store, &schema, &sst_path_gen, 1000).await;
+                manifest
+                    .add_file(sst.id(), sst.meta().clone())
+                    .await
+                    .unwrap();
+                sst.mark_compaction();
+                inputs.push(sst);
+            }
+            let task = Task {
+                inputs,
+                expireds: Vec::new(),
+            };
+
+            // Reading inputs costs 2s, and output adds more, so the task waits
+            // at least 1s after the burst quota is used up.
+            let io_rate_limit = task.input_size() / 2;
+            let (trigger_tx, _trigger_rx) = mpsc::channel(1);
+            let executor = Executor::new(
+                runtime.clone(),
+                store.clone(),
+                schema.clone(),
+                manifest.clone(),
+                sst_path_gen.clone(),
+                Arc::new(ParquetReader::new(store, schema, sst_path_gen)),
+                WriterProperties::default(),
+                u64::MAX, // mem_limit
+                Some(io_rate_limit),
+                trigger_tx,
+                EventNotifier::new(1),
+            )
+            .unwrap();
+            let begin = Instant::now();
+            executor.do_compaction(&task).await.unwrap();
+            let elapsed = begin.elapsed();
+            assert!(elapsed >= Duration::from_secs(1), "elapsed:{elapsed:?}");
+            assert_eq!(1, manifest.num_ssts().await);
+        });
+    }
+}
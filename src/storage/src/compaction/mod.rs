@@ -18,6 +18,7 @@
 mod executor;
 mod picker;
 mod scheduler;
+mod throttle;
 
 pub use scheduler::Scheduler as CompactionScheduler;
 
@@ -33,4 +34,10 @@ impl Task {
     pub fn input_size(&self) -> u64 {
         self.inputs.iter().map(|f| f.size() as u64).sum()
     }
+
+    /// Upper bound of the output size, since merging inputs only removes
+    /// duplicated rows.
+    pub fn max_output_size(&self) -> u64 {
+        self.input_size()
+    }
 }
@@ -54,9 +54,21 @@ impl Picker {
     /// multiple threads(that's why it take a mutable self).
     pub async fn pick_candidate(&mut self) -> Option<Task> {
         let ssts = self.manifest.all_ssts().await;
-        let expire_time = self.ttl.map(|ttl| (now() - ttl.as_micros() as i64).into());
+        let expire_time = self.expire_time();
         self.strategy.pick_candidate(ssts, expire_time)
     }
+
+    /// Like `pick_candidate`, but the picked files are not marked as in
+    /// compaction, so it's safe to call concurrently.
+    pub async fn plan_candidate(&self) -> Option<Task> {
+        let ssts = self.manifest.all_ssts().await;
+        let expire_time = self.expire_time();
+        self.strategy.plan_candidate(ssts, expire_time)
+    }
+
+    fn expire_time(&self) -> Option<Timestamp> {
+        self.ttl.map(|ttl| (now() - ttl.as_micros() as i64).into())
+    }
 }
 
 pub struct TimeWindowCompactionStrategy {
@@ -85,6 +97,25 @@ impl TimeWindowCompactionStrategy {
         &self,
         ssts: Vec<SstFile>,
         expire_time: Option<Timestamp>,
+    ) -> Option<Task> {
+        let task = self.plan_candidate(ssts, expire_time)?;
+        for f in &task.inputs {
+            f.mark_compaction();
+        }
+        for f in &task.expireds {
+            f.mark_compaction();
+        }
+
+        trace!(task = ?task, "End pick candidate");
+
+        Some(task)
+    }
+
+    /// Pick a candidate without marking files, used for dry-run.
+    pub fn plan_candidate(
+        &self,
+        ssts: Vec<SstFile>,
+        expire_time: Option<Timestamp>,
     ) -> Option<Task> {
         let (uncompacted_files, expired_files) =
             Self::find_uncompacted_and_expired_files(ssts, expire_time);
@@ -97,21 +128,10 @@ impl TimeWindowCompactionStrategy {
             return None;
         }
 
-        for f in &compaction_files {
-            f.mark_compaction();
-        }
-        for f in &expired_files {
-            f.mark_compaction();
-        }
-
-        let task = Task {
+        Some(Task {
             inputs: compaction_files,
             expireds: expired_files,
-        };
-
-        trace!(task = ?task, "End pick candidate");
-
-        Some(task)
+        })
     }
 
     fn find_uncompacted_and_expired_files(
@@ -216,9 +236,16 @@ mod tests {
                 )
             })
             .collect_vec();
+        let planned_task = strategy
+            .plan_candidate(ssts.clone(), Some(15.into()))
+            .unwrap();
+        // Dry-run should not mark files.
+        assert!(ssts.iter().all(|f| !f.is_compaction()));
+
         let task = strategy
             .pick_candidate(ssts.clone(), Some(15.into()))
             .unwrap();
+        assert_eq!(planned_task, task);
 
         // ssts should be grouped into three segments:
         // | 0 1 | 2 3 | 4 |
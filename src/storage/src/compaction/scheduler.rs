@@ -15,7 +15,13 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use anyhow::Context;
 use parquet::file::properties::WriterProperties;
@@ -24,14 +30,16 @@ use tokio::{
     task::JoinHandle,
     time::sleep,
 };
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 use super::{executor::Executor, picker::Picker};
 use crate::{
     compaction::Task,
     config::SchedulerConfig,
+    event::EventNotifier,
     manifest::ManifestRef,
     read::ParquetReader,
+    runtime::spawn_catch_panic,
     sst::SstPathGenerator,
     types::{ObjectStoreRef, RuntimeRef, StorageSchema},
     Result,
@@ -42,8 +50,11 @@ pub struct Scheduler {
     runtime: RuntimeRef,
 
     trigger_tx: Sender<()>,
-    task_handle: JoinHandle<()>,
-    picker_handle: JoinHandle<()>,
+    task_handle: JoinHandle<Result<()>>,
+    picker_handle: JoinHandle<Result<()>>,
+    // Only used for dry-run, tasks are picked in `picker_handle`.
+    planner: Picker,
+    enabled: Arc<AtomicBool>,
 }
 
 impl Scheduler {
@@ -58,9 +69,19 @@ impl Scheduler {
         parquet_reader: Arc<ParquetReader>,
         config: SchedulerConfig,
         write_props: WriterProperties,
-    ) -> Self {
+        event_notifier: EventNotifier,
+    ) -> Result<Self> {
         let (task_tx, task_rx) = mpsc::channel(config.max_pending_compaction_tasks);
         let (trigger_tx, trigger_rx) = mpsc::channel::<()>(1);
+        let enabled = Arc::new(AtomicBool::new(config.compaction_enabled));
+        let planner = Picker::new(
+            manifest.clone(),
+            config.ttl.map(|v| v.0),
+            segment_duration,
+            config.new_sst_max_size.0,
+            config.input_sst_max_num,
+            config.input_sst_min_num,
+        );
         let task_handle = {
             let store = store.clone();
             let manifest = manifest.clone();
@@ -73,15 +94,18 @@ impl Scheduler {
                 parquet_reader,
                 write_props,
                 config.memory_limit.0,
+                config.compaction_io_rate_limit.map(|v| v.0),
                 trigger_tx.clone(),
-            );
+                event_notifier,
+            )?;
 
-            runtime.spawn(async move {
+            spawn_catch_panic(&runtime, "compaction task loop", async move {
                 Self::recv_task_loop(task_rx, executor).await;
             })
         };
         let picker_handle = {
-            runtime.spawn(async move {
+            let enabled = enabled.clone();
+            spawn_catch_panic(&runtime, "compaction picker loop", async move {
                 let picker = Picker::new(
                     manifest,
                     config.ttl.map(|v| v.0),
@@ -90,19 +114,50 @@ impl Scheduler {
                     config.input_sst_max_num,
                     config.input_sst_min_num,
                 );
-                Self::generate_task_loop(task_tx, trigger_rx, picker, config.schedule_interval.0)
-                    .await;
+                Self::generate_task_loop(
+                    task_tx,
+                    trigger_rx,
+                    picker,
+                    config.schedule_interval.0,
+                    enabled,
+                )
+                .await;
             })
         };
 
-        Self {
+        Ok(Self {
             runtime,
             trigger_tx,
             task_handle,
             picker_handle,
+            planner,
+            enabled,
+        })
+    }
+
+    /// Pause or resume scheduling compaction tasks, running tasks are not
+    /// affected. Resuming triggers a compaction immediately.
+    pub fn set_enabled(&self, enabled: bool) {
+        let prev = self.enabled.swap(enabled, Ordering::Relaxed);
+        if prev != enabled {
+            info!(enabled, "Compaction enabled changed");
+            if enabled {
+                if let Err(e) = self.trigger_compaction() {
+                    debug!("Trigger compaction failed, err:{e}");
+                }
+            }
         }
     }
 
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the task the next compaction would pick, without executing it.
+    pub async fn compaction_plan(&self) -> Option<Task> {
+        self.planner.plan_candidate().await
+    }
+
     pub fn trigger_compaction(&self) -> Result<()> {
         self.trigger_tx
             .try_send(())
@@ -123,38 +178,39 @@ impl Scheduler {
         mut trigger_rx: Receiver<()>,
         mut picker: Picker,
         schedule_interval: Duration,
+        enabled: Arc<AtomicBool>,
     ) {
         info!(
             schedule_interval = ?schedule_interval,
             "Scheduler generate task loop started"
         );
-        let send_task = |task| {
-            if let Err(e) = task_tx.try_send(task) {
-                warn!("Send task failed, err:{e:?}");
-            }
-        };
-
         // Generate one task immediately
-        if let Some(task) = picker.pick_candidate().await {
-            send_task(task);
-        }
+        Self::pick_and_send(&task_tx, &mut picker, &enabled).await;
         loop {
             tokio::select! {
                 _ = sleep(schedule_interval) => {
-                    if let Some(task) = picker.pick_candidate().await {
-                        send_task(task);
-                    }
+                    Self::pick_and_send(&task_tx, &mut picker, &enabled).await;
                 }
                 signal = trigger_rx.recv() => {
                     if signal.is_none() {
                         info!("Scheduler generate task loop stopped");
                         return;
                     }
-                    if let Some(task) = picker.pick_candidate().await {
-                        send_task(task);
-                    }
+                    Self::pick_and_send(&task_tx, &mut picker, &enabled).await;
                 }
             }
         }
     }
+
+    async fn pick_and_send(task_tx: &Sender<Task>, picker: &mut Picker, enabled: &AtomicBool) {
+        if !enabled.load(Ordering::Relaxed) {
+            debug!("Compaction is disabled, skip picking task");
+            return;
+        }
+        if let Some(task) = picker.pick_candidate().await {
+            if let Err(e) = task_tx.try_send(task) {
+                warn!("Send task failed, err:{e:?}");
+            }
+        }
+    }
 }
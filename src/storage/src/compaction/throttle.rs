@@ -0,0 +1,93 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::time::sleep;
+
+use crate::{ensure, Result};
+
+/// Max duration of unused quota can be accumulated when idle.
+const MAX_BURST: Duration = Duration::from_secs(1);
+
+/// Byte-rate throttle shared by all compaction tasks.
+///
+/// Every acquire reserves a time slot after the previous reservations, and
+/// waits when the reserved slots go beyond `MAX_BURST` from now. So when the
+/// throttle is idle, up to `MAX_BURST` of quota is available at once, and
+/// short compactions after idle are not slowed down.
+pub struct IoThrottle {
+    bytes_per_sec: u64,
+    next_free: Mutex<Instant>,
+}
+
+impl IoThrottle {
+    pub fn try_new(bytes_per_sec: u64) -> Result<Self> {
+        ensure!(
+            bytes_per_sec > 0,
+            "compaction io rate limit should large than 0"
+        );
+
+        Ok(Self {
+            bytes_per_sec,
+            next_free: Mutex::new(Instant::now()),
+        })
+    }
+
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+
+    fn reserve(&self, bytes: u64) -> Duration {
+        let now = Instant::now();
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        let mut next_free = self.next_free.lock().unwrap();
+        *next_free = (*next_free).max(now) + cost;
+
+        // Requests within the burst quota are allowed immediately.
+        next_free.saturating_duration_since(now + MAX_BURST)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_io_throttle() {
+        assert!(IoThrottle::try_new(0).is_err());
+
+        let throttle = IoThrottle::try_new(10_000).unwrap();
+        let begin = Instant::now();
+        // Burst quota
+        throttle.acquire(10_000).await;
+        assert!(begin.elapsed() < Duration::from_millis(100));
+
+        for _ in 0..3 {
+            throttle.acquire(1_000).await;
+        }
+        let elapsed = begin.elapsed();
+        assert!(elapsed >= Duration::from_millis(300), "elapsed:{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(1), "elapsed:{elapsed:?}");
+    }
+}
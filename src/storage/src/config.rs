@@ -26,13 +26,25 @@ use serde::{Deserialize, Serialize};
 pub struct SchedulerConfig {
     pub schedule_interval: ReadableDuration,
     pub max_pending_compaction_tasks: usize,
+    // When disabled, no compaction task is scheduled, and ssts accumulate
+    // until it's enabled again.
+    pub compaction_enabled: bool,
     // Runner config
     pub memory_limit: ReadableSize,
+    // Bytes per second shared by all compaction tasks, `None` means no limit.
+    // Quota unused while idle is kept for at most 1s, so bursts after idle are
+    // bounded by 1s worth of bytes, not by how long it was idle.
+    pub compaction_io_rate_limit: Option<ReadableSize>,
     // Picker config
     pub ttl: Option<ReadableDuration>,
     pub new_sst_max_size: ReadableSize,
     pub input_sst_max_num: usize,
     pub input_sst_min_num: usize,
+    // Live sst limits
+    // Trigger compaction on write when sst num exceeds this value.
+    pub soft_sst_num_threshold: usize,
+    // Reject write when sst num exceeds this value.
+    pub hard_sst_num_threshold: Option<usize>,
 }
 
 impl Default for SchedulerConfig {
@@ -40,11 +52,15 @@ impl Default for SchedulerConfig {
         Self {
             schedule_interval: ReadableDuration::secs(10),
             max_pending_compaction_tasks: 10,
+            compaction_enabled: true,
             memory_limit: ReadableSize::gb(2_u64),
+            compaction_io_rate_limit: None,
             ttl: None,
             new_sst_max_size: ReadableSize::gb(1_u64),
             input_sst_max_num: 30,
             input_sst_min_num: 5,
+            soft_sst_num_threshold: 500,
+            hard_sst_num_threshold: None,
         }
     }
 }
@@ -115,6 +131,10 @@ pub struct WriteConfig {
     pub compression: ParquetCompression,
     // use to set column props with column name
     pub column_options: Option<HashMap<String, ColumnOptions>>,
+    // Max in-memory size of one write request, `None` means no limit.
+    pub max_write_batch_size: Option<ReadableSize>,
+    // Split oversized write into multiple ssts instead of rejecting it.
+    pub split_oversized_batch: bool,
 }
 
 impl Default for WriteConfig {
@@ -128,6 +148,8 @@ impl Default for WriteConfig {
             encoding: ParquetEncoding::Plain,
             compression: ParquetCompression::Snappy,
             column_options: None,
+            max_write_batch_size: None,
+            split_oversized_batch: false,
         }
     }
 }
@@ -140,6 +162,8 @@ pub struct ManifestConfig {
     pub min_merge_threshold: usize,
     pub hard_merge_threshold: usize,
     pub soft_merge_threshold: usize,
+    // Max retries when writing a delta file conflicts with an existing one.
+    pub max_update_retries: usize,
 }
 
 impl Default for ManifestConfig {
@@ -150,17 +174,67 @@ impl Default for ManifestConfig {
             min_merge_threshold: 10,
             soft_merge_threshold: 50,
             hard_merge_threshold: 90,
+            max_update_retries: 3,
         }
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(default, deny_unknown_fields)]
 pub struct StorageConfig {
     pub write: WriteConfig,
     pub manifest: ManifestConfig,
     pub scheduler: SchedulerConfig,
     pub update_mode: UpdateMode,
+    pub event_channel_size: usize,
+    // Report orphaned ssts periodically, `None` means disabled.
+    pub orphaned_sst_check_interval: Option<ReadableDuration>,
+    // Orphaned ssts younger than this are never deleted, since they may belong
+    // to in-flight writes or compactions.
+    pub orphaned_sst_min_age: ReadableDuration,
+    // Reject scan whose time range is wider than this, `None` means no limit.
+    pub max_scan_time_range: Option<ReadableDuration>,
+    // Max concurrent object store requests issued by this storage, excess
+    // requests wait in queue. `None` means no limit.
+    pub max_concurrent_object_store_requests: Option<usize>,
+    // Cache sst ranges read from object store on local disk, `None` means
+    // disabled.
+    pub sst_disk_cache: Option<DiskCacheConfig>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            write: WriteConfig::default(),
+            manifest: ManifestConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            update_mode: UpdateMode::default(),
+            event_channel_size: 64,
+            orphaned_sst_check_interval: None,
+            orphaned_sst_min_age: ReadableDuration::hours(1),
+            max_scan_time_range: None,
+            max_concurrent_object_store_requests: None,
+            sst_disk_cache: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiskCacheConfig {
+    // Cleared on start.
+    pub dir: String,
+    // Max total size of cached ranges.
+    pub capacity: ReadableSize,
+}
+
+impl Default for DiskCacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: "/tmp/horaedb/sst_cache".to_string(),
+            capacity: ReadableSize::gb(10_u64),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq)]
@@ -169,4 +243,7 @@ pub enum UpdateMode {
     #[default]
     Overwrite,
     Append,
+    /// Keep at most N latest versions of each primary key, `MaxVersions(1)` is
+    /// the same as `Overwrite`.
+    MaxVersions(usize),
 }
@@ -0,0 +1,471 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Object store wrapper caching sst byte ranges on local disk, so repeated
+//! reads of the same ssts don't go to the remote store.
+
+use std::{
+    collections::{hash_map, BTreeMap, HashMap},
+    fmt,
+    ops::Range,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use object_store::{
+    path::Path, GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOpts, PutOptions, PutPayload, PutResult,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::DiskCacheConfig,
+    ensure,
+    sst::{FileId, SstPathGenerator},
+    types::ObjectStoreRef,
+    Result,
+};
+
+/// Ssts are immutable, so a cached range never goes stale.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    id: FileId,
+    range: Range<usize>,
+}
+
+impl CacheKey {
+    /// Returns `None` for non-sst files, which are not cached.
+    fn new(location: &Path, range: &Range<usize>) -> Option<Self> {
+        let id = SstPathGenerator::parse_id(location.filename()?)?;
+        Some(Self {
+            id,
+            range: range.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    // Every cached file gets a unique name, so removing an evicted file never
+    // touches a newer file of the same key.
+    file_seq: u64,
+    last_access: u64,
+    size: usize,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<CacheKey, Entry>,
+    // Access sequence -> key, the first one is the least recently used.
+    accesses: BTreeMap<u64, CacheKey>,
+    next_seq: u64,
+    used: usize,
+}
+
+impl Lru {
+    fn next_seq(&mut self) -> u64 {
+        self.next_seq += 1;
+        self.next_seq
+    }
+
+    /// Returns file seq of the key, and marks it as recently used.
+    fn touch(&mut self, key: &CacheKey) -> Option<u64> {
+        let seq = self.next_seq();
+        let entry = self.entries.get_mut(key)?;
+        self.accesses.remove(&entry.last_access);
+        entry.last_access = seq;
+        self.accesses.insert(seq, key.clone());
+        Some(entry.file_seq)
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.accesses.remove(&entry.last_access);
+        self.used -= entry.size;
+        Some(entry)
+    }
+
+    /// Returns evicted entries, whose files should be removed.
+    fn evict(&mut self, capacity: usize) -> Vec<(CacheKey, Entry)> {
+        let mut evicted = Vec::new();
+        while self.used > capacity {
+            let Some((_, key)) = self.accesses.pop_first() else {
+                break;
+            };
+            let entry = self.entries.remove(&key).unwrap();
+            self.used -= entry.size;
+            evicted.push((key, entry));
+        }
+        evicted
+    }
+}
+
+/// Ranged reads of ssts are served from local disk when cached, and cached
+/// after fetched from `inner` otherwise, least recently used ranges are
+/// evicted when total size exceeds `capacity`.
+///
+/// Other requests go to `inner` directly. Cache files are removed on start,
+/// since the index is only kept in memory.
+pub struct DiskCacheStore {
+    inner: ObjectStoreRef,
+    dir: PathBuf,
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+impl DiskCacheStore {
+    pub fn try_new(inner: ObjectStoreRef, config: &DiskCacheConfig) -> Result<Self> {
+        ensure!(
+            config.capacity.as_byte() > 0,
+            "disk cache capacity should large than 0"
+        );
+        let dir = PathBuf::from(&config.dir);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)
+                .with_context(|| format!("clean disk cache dir, dir:{}", config.dir))?;
+        }
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("create disk cache dir, dir:{}", config.dir))?;
+
+        Ok(Self {
+            inner,
+            dir,
+            capacity: config.capacity.as_byte() as usize,
+            lru: Mutex::new(Lru::default()),
+        })
+    }
+
+    fn file_path(&self, key: &CacheKey, file_seq: u64) -> PathBuf {
+        self.dir.join(format!(
+            "{}-{}-{}-{file_seq}",
+            key.id, key.range.start, key.range.end
+        ))
+    }
+
+    /// Returns `None` when not cached, or the cached file is gone, e.g.
+    /// evicted after the lookup.
+    async fn read(&self, key: &CacheKey) -> Option<Bytes> {
+        let file_seq = self.lru.lock().unwrap().touch(key)?;
+        let path = self.file_path(key, file_seq);
+        match tokio::fs::read(&path).await {
+            Ok(data) if data.len() == key.range.len() => return Some(data.into()),
+            Ok(data) => warn!(
+                path = ?path,
+                expected = key.range.len(),
+                actual = data.len(),
+                "Disk cache file size mismatch"
+            ),
+            Err(e) => debug!(path = ?path, "Read disk cache failed, err:{e}"),
+        }
+        let mut lru = self.lru.lock().unwrap();
+        if lru.entries.get(key).is_some_and(|v| v.file_seq == file_seq) {
+            lru.remove(key);
+        }
+        None
+    }
+
+    async fn insert(&self, key: CacheKey, data: &Bytes) {
+        if data.len() > self.capacity {
+            return;
+        }
+        let file_seq = self.lru.lock().unwrap().next_seq();
+        let path = self.file_path(&key, file_seq);
+        if let Err(e) = tokio::fs::write(&path, data).await {
+            warn!(path = ?path, "Write disk cache failed, err:{e}");
+            Self::remove_file(path).await;
+            return;
+        }
+
+        let to_removes = {
+            let mut lru = self.lru.lock().unwrap();
+            let seq = lru.next_seq();
+            match lru.entries.entry(key.clone()) {
+                // Cached by a concurrent read.
+                hash_map::Entry::Occupied(_) => vec![path],
+                hash_map::Entry::Vacant(v) => {
+                    v.insert(Entry {
+                        file_seq,
+                        last_access: seq,
+                        size: data.len(),
+                    });
+                    lru.accesses.insert(seq, key);
+                    lru.used += data.len();
+                    lru.evict(self.capacity)
+                        .into_iter()
+                        .map(|(key, entry)| self.file_path(&key, entry.file_seq))
+                        .collect()
+                }
+            }
+        };
+        for path in to_removes {
+            Self::remove_file(path).await;
+        }
+    }
+
+    async fn remove_file(path: PathBuf) {
+        match tokio::fs::remove_file(&path).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(path = ?path, "Remove disk cache file failed, err:{e}"),
+        }
+    }
+
+    /// Returns number of cached ranges and their total size.
+    pub fn usage(&self) -> (usize, usize) {
+        let lru = self.lru.lock().unwrap();
+        (lru.entries.len(), lru.used)
+    }
+}
+
+impl fmt::Debug for DiskCacheStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskCacheStore")
+            .field("inner", &self.inner)
+            .field("dir", &self.dir)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl fmt::Display for DiskCacheStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DiskCacheStore({:?}, {})", self.dir, self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for DiskCacheStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.inner.get_opts(location, options).await
+    }
+
+    // Sst reader fetches data by ranges, `get_ranges` is built on this too.
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> object_store::Result<Bytes> {
+        let Some(key) = CacheKey::new(location, &range) else {
+            return self.inner.get_range(location, range).await;
+        };
+        if let Some(data) = self.read(&key).await {
+            return Ok(data);
+        }
+
+        let data = self.inner.get_range(location, range).await?;
+        self.insert(key, &data).await;
+        Ok(data)
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.inner.delete(location).await?;
+        // Ids are never reused, this only frees space early.
+        let Some(id) = location.filename().and_then(SstPathGenerator::parse_id) else {
+            return Ok(());
+        };
+        let removed = {
+            let mut lru = self.lru.lock().unwrap();
+            let keys = lru
+                .entries
+                .keys()
+                .filter(|key| key.id == id)
+                .cloned()
+                .collect::<Vec<_>>();
+            keys.into_iter()
+                .filter_map(|key| lru.remove(&key).map(|entry| (key, entry)))
+                .collect::<Vec<_>>()
+        };
+        for (key, entry) in removed {
+            Self::remove_file(self.file_path(&key, entry.file_seq)).await;
+        }
+        Ok(())
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::Ordering, Arc};
+
+    use common::ReadableSize;
+
+    use super::*;
+    use crate::test_util::MockObjectStore;
+
+    struct TestContext {
+        _root_dir: temp_dir::TempDir,
+        remote: Arc<MockObjectStore>,
+        store: Arc<DiskCacheStore>,
+        sst_path_gen: SstPathGenerator,
+    }
+
+    impl TestContext {
+        fn new(capacity: u64) -> Self {
+            let root_dir = temp_dir::TempDir::new().unwrap();
+            let remote = Arc::new(MockObjectStore::new());
+            let config = DiskCacheConfig {
+                dir: root_dir.path().join("cache").to_string_lossy().to_string(),
+                capacity: ReadableSize(capacity),
+            };
+            let store = Arc::new(DiskCacheStore::try_new(remote.clone(), &config).unwrap());
+            let sst_path_gen = SstPathGenerator::new(root_dir.path().to_string_lossy().to_string());
+            Self {
+                _root_dir: root_dir,
+                remote,
+                store,
+                sst_path_gen,
+            }
+        }
+
+        async fn put_sst(&self, id: FileId, data: &'static str) -> Path {
+            let path = Path::from(self.sst_path_gen.generate(id));
+            self.store.put(&path, data.into()).await.unwrap();
+            path
+        }
+
+        fn num_remote_gets(&self) -> usize {
+            self.remote.num_gets.load(Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_hit() {
+        let ctx = TestContext::new(1024);
+        let path = ctx.put_sst(1, "0123456789").await;
+
+        assert_eq!("234", ctx.store.get_range(&path, 2..5).await.unwrap());
+        assert_eq!(1, ctx.num_remote_gets());
+        assert_eq!((1, 3), ctx.store.usage());
+        // Served from disk.
+        assert_eq!("234", ctx.store.get_range(&path, 2..5).await.unwrap());
+        assert_eq!(1, ctx.num_remote_gets());
+
+        // Keys include the range.
+        assert_eq!("2345", ctx.store.get_range(&path, 2..6).await.unwrap());
+        assert_eq!(2, ctx.num_remote_gets());
+        assert_eq!((2, 7), ctx.store.usage());
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_evict_lru() {
+        let ctx = TestContext::new(6);
+        let path = ctx.put_sst(1, "0123456789").await;
+
+        ctx.store.get_range(&path, 0..3).await.unwrap();
+        ctx.store.get_range(&path, 3..6).await.unwrap();
+        // Make 3..6 the least recently used.
+        ctx.store.get_range(&path, 0..3).await.unwrap();
+        assert_eq!(2, ctx.num_remote_gets());
+        ctx.store.get_range(&path, 6..9).await.unwrap();
+        assert_eq!(3, ctx.num_remote_gets());
+        assert_eq!((2, 6), ctx.store.usage());
+
+        ctx.store.get_range(&path, 0..3).await.unwrap();
+        assert_eq!(3, ctx.num_remote_gets());
+        assert_eq!("345", ctx.store.get_range(&path, 3..6).await.unwrap());
+        assert_eq!(4, ctx.num_remote_gets());
+
+        // Larger than capacity, never cached.
+        ctx.store.get_range(&path, 0..7).await.unwrap();
+        assert_eq!((2, 6), ctx.store.usage());
+        // Only files of cached ranges are left.
+        assert_eq!(2, std::fs::read_dir(&ctx.store.dir).unwrap().count());
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_concurrent_reads() {
+        let ctx = TestContext::new(8);
+        let path = ctx.put_sst(1, "0123456789").await;
+
+        let reads = (0..100).map(|i| {
+            let start = i % 5;
+            let store = ctx.store.clone();
+            let path = path.clone();
+            tokio::spawn(async move {
+                let data = store.get_range(&path, start..start + 4).await.unwrap();
+                assert_eq!(&b"0123456789"[start..start + 4], data);
+            })
+        });
+        for res in futures::future::join_all(reads).await {
+            res.unwrap();
+        }
+        let (_, used) = ctx.store.usage();
+        assert!(used <= 8);
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_skip_non_sst() {
+        let ctx = TestContext::new(1024);
+        let path = Path::from(format!("{}/manifest", ctx.sst_path_gen.data_dir()));
+        ctx.store.put(&path, "manifest".into()).await.unwrap();
+
+        ctx.store.get_range(&path, 0..3).await.unwrap();
+        ctx.store.get_range(&path, 0..3).await.unwrap();
+        assert_eq!(2, ctx.num_remote_gets());
+        assert_eq!((0, 0), ctx.store.usage());
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_delete_sst() {
+        let ctx = TestContext::new(1024);
+        let path1 = ctx.put_sst(1, "0123456789").await;
+        let path2 = ctx.put_sst(2, "0123456789").await;
+        ctx.store.get_range(&path1, 0..3).await.unwrap();
+        ctx.store.get_range(&path1, 3..6).await.unwrap();
+        ctx.store.get_range(&path2, 0..3).await.unwrap();
+
+        ctx.store.delete(&path1).await.unwrap();
+        assert_eq!((1, 3), ctx.store.usage());
+        assert!(ctx.store.get_range(&path1, 0..3).await.is_err());
+    }
+}
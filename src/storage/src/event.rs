@@ -0,0 +1,65 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tracing::trace;
+
+use crate::sst::{FileId, SstFile};
+
+/// Events emitted after ssts of the storage are changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageEvent {
+    /// A new sst is created by write.
+    SstCreated(SstFile),
+    /// A compaction task is committed to manifest, `inputs` are replaced by
+    /// `output` and `expireds` are dropped.
+    CompactionCompleted {
+        inputs: Vec<FileId>,
+        expireds: Vec<FileId>,
+        output: SstFile,
+    },
+    /// Ssts are deleted from object store.
+    SstPurged(Vec<FileId>),
+}
+
+/// Broadcast storage events to subscribers.
+///
+/// The channel is bounded, a slow subscriber will miss the oldest events and
+/// receive `RecvError::Lagged` with the number of dropped events, writers are
+/// never blocked.
+#[derive(Debug, Clone)]
+pub struct EventNotifier {
+    sender: Sender<StorageEvent>,
+}
+
+impl EventNotifier {
+    pub fn new(channel_size: usize) -> Self {
+        let (sender, _) = broadcast::channel(channel_size);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> Receiver<StorageEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn notify(&self, event: StorageEvent) {
+        // Send only fails when there is no subscriber.
+        if let Err(e) = self.sender.send(event) {
+            trace!(event = ?e.0, "No subscriber for storage event");
+        }
+    }
+}
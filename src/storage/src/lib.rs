@@ -20,10 +20,15 @@
 #![feature(duration_constructors)]
 mod compaction;
 pub mod config;
+pub mod disk_cache;
+pub mod event;
+pub mod limit_store;
 mod macros;
 pub mod manifest;
 pub mod operator;
+pub mod orphan;
 mod read;
+pub mod runtime;
 pub mod sst;
 pub mod storage;
 #[cfg(test)]
@@ -0,0 +1,291 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Object store wrapper bounding concurrent requests, with observable
+//! in-flight and waiting counts.
+
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures::{stream::BoxStream, FutureExt, Stream, StreamExt};
+use object_store::{
+    path::Path, GetOptions, GetResult, GetResultPayload, ListResult, MultipartUpload, ObjectMeta,
+    ObjectStore, PutMultipartOpts, PutOptions, PutPayload, PutResult, UploadPart,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{ensure, types::ObjectStoreRef, Result};
+
+/// Point-in-time request counts of a [`LimitStore`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LimitStoreStats {
+    pub in_flight: usize,
+    pub waiting: usize,
+}
+
+/// Requests beyond `max_requests` wait in queue. Streams returned by get and
+/// list hold their permit until dropped.
+#[derive(Debug)]
+pub struct LimitStore {
+    inner: ObjectStoreRef,
+    permits: Arc<Permits>,
+}
+
+#[derive(Debug)]
+struct Permits {
+    max_requests: usize,
+    semaphore: Arc<Semaphore>,
+    waiting: AtomicUsize,
+}
+
+impl Permits {
+    async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        // Decrease waiting even if the request is cancelled.
+        let _waiting = WaitingGuard(&self.waiting);
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed")
+    }
+}
+
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl LimitStore {
+    pub fn try_new(inner: ObjectStoreRef, max_requests: usize) -> Result<Self> {
+        ensure!(
+            max_requests > 0,
+            "max concurrent object store requests should large than 0"
+        );
+
+        Ok(Self {
+            inner,
+            permits: Arc::new(Permits {
+                max_requests,
+                semaphore: Arc::new(Semaphore::new(max_requests)),
+                waiting: AtomicUsize::new(0),
+            }),
+        })
+    }
+
+    pub fn stats(&self) -> LimitStoreStats {
+        LimitStoreStats {
+            in_flight: self.permits.max_requests - self.permits.semaphore.available_permits(),
+            waiting: self.permits.waiting.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl fmt::Display for LimitStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LimitStore({}, {})",
+            self.permits.max_requests, self.inner
+        )
+    }
+}
+
+// Methods with default implementation, such as `head` and `get_range`, are
+// built on the ones below, so they are limited too.
+#[async_trait]
+impl ObjectStore for LimitStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        let _permit = self.permits.acquire().await;
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        let upload = {
+            let _permit = self.permits.acquire().await;
+            self.inner.put_multipart_opts(location, opts).await?
+        };
+        Ok(Box::new(LimitUpload {
+            upload,
+            permits: self.permits.clone(),
+        }))
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let permit = self.permits.acquire().await;
+        let res = self.inner.get_opts(location, options).await?;
+        let payload = match res.payload {
+            v @ GetResultPayload::File(_, _) => v,
+            GetResultPayload::Stream(s) => {
+                GetResultPayload::Stream(PermitStream::new(s, permit).boxed())
+            }
+        };
+        Ok(GetResult { payload, ..res })
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        let _permit = self.permits.acquire().await;
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        let prefix = prefix.cloned();
+        self.permits
+            .acquire()
+            .map(move |permit| PermitStream::new(self.inner.list(prefix.as_ref()), permit))
+            .into_stream()
+            .flatten()
+            .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        let _permit = self.permits.acquire().await;
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        let _permit = self.permits.acquire().await;
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        let _permit = self.permits.acquire().await;
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+struct PermitStream<T> {
+    inner: T,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<T> PermitStream<T> {
+    fn new(inner: T, permit: OwnedSemaphorePermit) -> Self {
+        Self {
+            inner,
+            _permit: permit,
+        }
+    }
+}
+
+impl<T: Stream + Unpin> Stream for PermitStream<T> {
+    type Item = T::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[derive(Debug)]
+struct LimitUpload {
+    upload: Box<dyn MultipartUpload>,
+    permits: Arc<Permits>,
+}
+
+#[async_trait]
+impl MultipartUpload for LimitUpload {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        let upload = self.upload.put_part(data);
+        let permits = self.permits.clone();
+        Box::pin(async move {
+            let _permit = permits.acquire().await;
+            upload.await
+        })
+    }
+
+    async fn complete(&mut self) -> object_store::Result<PutResult> {
+        let _permit = self.permits.acquire().await;
+        self.upload.complete().await
+    }
+
+    async fn abort(&mut self) -> object_store::Result<()> {
+        let _permit = self.permits.acquire().await;
+        self.upload.abort().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_util::MockObjectStore;
+
+    #[tokio::test]
+    async fn test_limit_store_stats() {
+        let root_dir = temp_dir::TempDir::new().unwrap();
+        let mock = MockObjectStore::new().with_get_delay(Duration::from_millis(200));
+        let store = Arc::new(LimitStore::try_new(Arc::new(mock), 1).unwrap());
+        let path = Path::from(format!("{}/a", root_dir.path().to_string_lossy()));
+        store.put(&path, "data".into()).await.unwrap();
+        assert_eq!(LimitStoreStats::default(), store.stats());
+
+        let handles = (0..3)
+            .map(|_| {
+                let store = store.clone();
+                let path = path.clone();
+                tokio::spawn(async move { store.get(&path).await.unwrap().bytes().await })
+            })
+            .collect::<Vec<_>>();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            LimitStoreStats {
+                in_flight: 1,
+                waiting: 2
+            },
+            store.stats()
+        );
+
+        for handle in handles {
+            assert_eq!("data", handle.await.unwrap().unwrap());
+        }
+        assert_eq!(LimitStoreStats::default(), store.stats());
+    }
+
+    #[test]
+    fn test_reject_zero_limit() {
+        assert!(LimitStore::try_new(Arc::new(MockObjectStore::new()), 0).is_err());
+    }
+}
@@ -30,16 +30,17 @@ use bytes::Bytes;
 pub use encoding::{ManifestUpdate, Snapshot};
 use futures::{StreamExt, TryStreamExt};
 use itertools::Itertools;
-use object_store::{path::Path, PutPayload};
+use object_store::{path::Path, PutMode, PutPayload};
 use prost::Message;
 use tokio::sync::{
     mpsc::{self, Receiver, Sender},
     RwLock,
 };
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     config::ManifestConfig,
+    runtime::spawn_catch_panic,
     sst::{FileId, FileMeta, SstFile},
     types::{ObjectStoreRef, RuntimeRef, TimeRange},
     AnyhowError, Result,
@@ -68,6 +69,7 @@ pub struct Manifest {
     delta_dir: Path,
     store: ObjectStoreRef,
     merger: Arc<ManifestMerger>,
+    max_update_retries: usize,
 
     ssts: RwLock<Vec<SstFile>>,
 }
@@ -81,6 +83,7 @@ impl Manifest {
     ) -> Result<Self> {
         let snapshot_path = Path::from(format!("{root_dir}/{PREFIX_PATH}/{SNAPSHOT_FILENAME}"));
         let delta_dir = Path::from(format!("{root_dir}/{PREFIX_PATH}/{DELTA_PREFIX}"));
+        let max_update_retries = merge_options.max_update_retries;
 
         let merger = ManifestMerger::try_new(
             snapshot_path.clone(),
@@ -99,7 +102,7 @@ impl Manifest {
         {
             let merger = merger.clone();
             // Start merger in background
-            runtime.spawn(async move {
+            spawn_catch_panic(&runtime, "manifest merger", async move {
                 merger.run().await;
             });
         }
@@ -108,6 +111,7 @@ impl Manifest {
             delta_dir,
             store,
             merger,
+            max_update_retries,
             ssts: RwLock::new(ssts),
         })
     }
@@ -129,7 +133,6 @@ impl Manifest {
     }
 
     pub async fn update_inner(&self, update: ManifestUpdate) -> Result<()> {
-        let path = Path::from(format!("{}/{}", self.delta_dir, Self::allocate_id()));
         let pb_update = pb_types::ManifestUpdate::from(update.clone());
         let mut buf: Vec<u8> = Vec::with_capacity(pb_update.encoded_len());
         pb_update
@@ -137,10 +140,7 @@ impl Manifest {
             .context("failed to encode manifest update")?;
 
         // 1. Persist the delta manifest
-        self.store
-            .put(&path, PutPayload::from_bytes(Bytes::from(buf)))
-            .await
-            .with_context(|| format!("Failed to write delta manifest, path:{}", path))?;
+        self.put_delta(Bytes::from(buf)).await?;
 
         // 2. Update cached payload
         {
@@ -156,12 +156,82 @@ impl Manifest {
         Ok(())
     }
 
+    /// Delta files are created with `PutMode::Create`, so a conflicting delta
+    /// (e.g. id collision after server time goes backwards) is detected
+    /// instead of being overwritten silently. On conflict a new id is
+    /// allocated and the write is retried, at most `max_update_retries` times.
+    ///
+    /// A conflict may also be reported for our own write, e.g. when the store
+    /// retries a put whose first attempt succeeded, so the existing delta is
+    /// read back and accepted if it equals `payload`.
+    async fn put_delta(&self, payload: Bytes) -> Result<Path> {
+        let mut retries = 0;
+        loop {
+            let path = Path::from(format!("{}/{}", self.delta_dir, Self::allocate_id()));
+            let res = self
+                .store
+                .put_opts(
+                    &path,
+                    PutPayload::from_bytes(payload.clone()),
+                    PutMode::Create.into(),
+                )
+                .await;
+            match res {
+                Ok(_) => return Ok(path),
+                Err(object_store::Error::AlreadyExists { .. })
+                    if self.is_delta_written(&path, &payload).await =>
+                {
+                    debug!(path = %path, "Delta manifest already written");
+                    return Ok(path);
+                }
+                Err(object_store::Error::AlreadyExists { .. })
+                    if retries < self.max_update_retries =>
+                {
+                    retries += 1;
+                    warn!(path = %path, retries, "Delta manifest already exists, retry with new id");
+                }
+                // Conditional put is not supported by every store, fallback to
+                // overwrite since the id is unique in most cases.
+                Err(object_store::Error::NotImplemented) => {
+                    self.store
+                        .put(&path, PutPayload::from_bytes(payload))
+                        .await
+                        .with_context(|| format!("Failed to write delta manifest, path:{path}"))?;
+                    return Ok(path);
+                }
+                Err(err) => {
+                    let context =
+                        format!("Failed to write delta manifest, path:{path}, retries:{retries}");
+                    return Err(AnyhowError::new(err).context(context).into());
+                }
+            }
+        }
+    }
+
+    async fn is_delta_written(&self, path: &Path, payload: &Bytes) -> bool {
+        let res = match self.store.get(path).await {
+            Ok(res) => res.bytes().await,
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(bytes) => bytes == payload,
+            Err(e) => {
+                warn!(path = %path, "Failed to read existing delta manifest, err:{e}");
+                false
+            }
+        }
+    }
+
     // TODO: avoid clone
     pub async fn all_ssts(&self) -> Vec<SstFile> {
         let ssts = self.ssts.read().await;
         ssts.clone()
     }
 
+    pub async fn num_ssts(&self) -> usize {
+        self.ssts.read().await.len()
+    }
+
     pub async fn find_ssts(&self, time_range: &TimeRange) -> Vec<SstFile> {
         let ssts = self.ssts.read().await;
 
@@ -401,6 +471,7 @@ mod tests {
     use tokio::time::sleep;
 
     use super::*;
+    use crate::test_util::MockObjectStore;
 
     #[test]
     fn test_find_manifest() {
@@ -506,4 +577,111 @@ mod tests {
             assert!(delta_paths.is_empty());
         })
     }
+
+    #[test]
+    fn test_update_retry_on_conflict() {
+        let root_dir = temp_dir::TempDir::new().unwrap();
+        let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+        let rt = runtime.clone();
+
+        rt.block_on(async move {
+            let root_dir = root_dir.path().to_string_lossy().to_string();
+            let delta_dir = Path::from(format!("{root_dir}/{PREFIX_PATH}/{DELTA_PREFIX}"));
+            let store: ObjectStoreRef = Arc::new(MockObjectStore::new().with_put_conflicts(2));
+            let manifest = Manifest::try_new(
+                root_dir,
+                store.clone(),
+                runtime.clone(),
+                ManifestConfig {
+                    max_update_retries: 2,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+            let meta = FileMeta {
+                max_sequence: 1,
+                num_rows: 1,
+                size: 1,
+                time_range: (1..2).into(),
+            };
+            manifest.add_file(1, meta.clone()).await.unwrap();
+            assert_eq!(vec![SstFile::new(1, meta)], manifest.all_ssts().await);
+
+            // Conflicted puts leave nothing behind, so the update is only
+            // persisted once.
+            let delta_paths = list_delta_paths(&store, &delta_dir).await.unwrap();
+            assert_eq!(1, delta_paths.len());
+        });
+    }
+
+    #[test]
+    fn test_update_retry_exhausted() {
+        let root_dir = temp_dir::TempDir::new().unwrap();
+        let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+        let rt = runtime.clone();
+
+        rt.block_on(async move {
+            let store: ObjectStoreRef = Arc::new(MockObjectStore::new().with_put_conflicts(3));
+            let manifest = Manifest::try_new(
+                root_dir.path().to_string_lossy().to_string(),
+                store,
+                runtime.clone(),
+                ManifestConfig {
+                    max_update_retries: 2,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+            let meta = FileMeta {
+                max_sequence: 1,
+                num_rows: 1,
+                size: 1,
+                time_range: (1..2).into(),
+            };
+            assert!(manifest.add_file(1, meta).await.is_err());
+            assert!(manifest.all_ssts().await.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_update_conflict_after_write() {
+        let root_dir = temp_dir::TempDir::new().unwrap();
+        let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+        let rt = runtime.clone();
+
+        rt.block_on(async move {
+            let root_dir = root_dir.path().to_string_lossy().to_string();
+            let delta_dir = Path::from(format!("{root_dir}/{PREFIX_PATH}/{DELTA_PREFIX}"));
+            let store: ObjectStoreRef = Arc::new(
+                MockObjectStore::new()
+                    .with_put_conflicts(1)
+                    .with_write_on_conflict(),
+            );
+            let manifest = Manifest::try_new(
+                root_dir,
+                store.clone(),
+                runtime.clone(),
+                ManifestConfig::default(),
+            )
+            .await
+            .unwrap();
+
+            let meta = FileMeta {
+                max_sequence: 1,
+                num_rows: 1,
+                size: 1,
+                time_range: (1..2).into(),
+            };
+            manifest.add_file(1, meta.clone()).await.unwrap();
+            assert_eq!(vec![SstFile::new(1, meta)], manifest.all_ssts().await);
+
+            // The written delta is accepted instead of retried with a new id.
+            let delta_paths = list_delta_paths(&store, &delta_dir).await.unwrap();
+            assert_eq!(1, delta_paths.len());
+        });
+    }
 }
@@ -43,6 +43,27 @@ impl MergeOperator for LastValueOperator {
     }
 }
 
+/// Keep the last N rows, which are the latest N versions since rows are sorted
+/// by sequence.
+#[derive(Debug)]
+pub struct LastNValuesOperator {
+    n: usize,
+}
+
+impl LastNValuesOperator {
+    pub fn new(n: usize) -> Self {
+        Self { n }
+    }
+}
+
+impl MergeOperator for LastNValuesOperator {
+    fn merge(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let num_rows = batch.num_rows();
+        let offset = num_rows.saturating_sub(self.n);
+        Ok(batch.slice(offset, num_rows - offset))
+    }
+}
+
 #[derive(Debug)]
 pub struct BytesMergeOperator {
     /// Column index of the column need to append together
@@ -136,6 +157,30 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_last_n_values_operator() {
+        let batch = record_batch!(
+            ("pk1", UInt8, vec![11, 11, 11, 11]),
+            ("value", Int64, vec![2, 7, 4, 1])
+        )
+        .unwrap();
+
+        let actual = LastNValuesOperator::new(1).merge(batch.clone()).unwrap();
+        let expected = record_batch!(("pk1", UInt8, vec![11]), ("value", Int64, vec![1])).unwrap();
+        assert_eq!(actual, expected);
+
+        let actual = LastNValuesOperator::new(3).merge(batch.clone()).unwrap();
+        let expected = record_batch!(
+            ("pk1", UInt8, vec![11, 11, 11]),
+            ("value", Int64, vec![7, 4, 1])
+        )
+        .unwrap();
+        assert_eq!(actual, expected);
+
+        let actual = LastNValuesOperator::new(10).merge(batch.clone()).unwrap();
+        assert_eq!(actual, batch);
+    }
+
     #[test]
     fn test_bytes_merge_operator() {
         let operator = BytesMergeOperator::new(vec![2]);
@@ -0,0 +1,195 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Detect sst files which exist in object store but are not referenced by
+//! manifest, they are usually left by crashes during write or compaction.
+//!
+//! Ssts referenced by manifest but missing in object store are detected too.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use futures::TryStreamExt;
+use object_store::path::Path;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::{
+    event::EventNotifier,
+    manifest::ManifestRef,
+    sst::{purge_ssts, FileId, SstPathGenerator},
+    types::ObjectStoreRef,
+    Result,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanedSst {
+    pub id: FileId,
+    pub path: String,
+    pub size: usize,
+    /// Time since the file is last modified.
+    pub age: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// Ssts referenced by manifest but not found in object store, queries
+    /// touching them will fail.
+    ///
+    /// Ssts deleted by compaction during the check may be reported here too.
+    pub missing_ssts: Vec<FileId>,
+    pub orphaned_ssts: Vec<OrphanedSst>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing_ssts.is_empty() && self.orphaned_ssts.is_empty()
+    }
+}
+
+pub struct OrphanDetector {
+    store: ObjectStoreRef,
+    manifest: ManifestRef,
+    sst_path_gen: Arc<SstPathGenerator>,
+    event_notifier: EventNotifier,
+    min_age: Duration,
+}
+
+impl OrphanDetector {
+    pub fn new(
+        store: ObjectStoreRef,
+        manifest: ManifestRef,
+        sst_path_gen: Arc<SstPathGenerator>,
+        event_notifier: EventNotifier,
+        min_age: Duration,
+    ) -> Self {
+        Self {
+            store,
+            manifest,
+            sst_path_gen,
+            event_notifier,
+            min_age,
+        }
+    }
+
+    /// Ssts being written are not in manifest yet, so they are reported too,
+    /// with a small age.
+    pub async fn find(&self) -> Result<Vec<OrphanedSst>> {
+        Ok(self.check().await?.orphaned_ssts)
+    }
+
+    /// Compare ssts in manifest with files in object store, nothing is
+    /// modified.
+    pub async fn check(&self) -> Result<ConsistencyReport> {
+        let data_dir = Path::from(self.sst_path_gen.data_dir());
+        // Take live ssts before listing, so an sst added in between is seen as
+        // orphan, instead of a deleted one being missed.
+        // Live ids found in object store are removed, the rest are missing.
+        let mut missing_ids = self
+            .manifest
+            .all_ssts()
+            .await
+            .iter()
+            .map(|sst| sst.id())
+            .collect::<HashSet<_>>();
+        let objects = self
+            .store
+            .list(Some(&data_dir))
+            .try_collect::<Vec<_>>()
+            .await
+            .with_context(|| format!("list sst files, dir:{data_dir}"))?;
+
+        let now = common::now();
+        let mut orphans = objects
+            .into_iter()
+            .filter_map(|object| {
+                let id = SstPathGenerator::parse_id(object.location.filename()?)?;
+                if missing_ids.remove(&id) {
+                    return None;
+                }
+                let age_ms = (now - object.last_modified.timestamp_millis()).max(0);
+                Some(OrphanedSst {
+                    id,
+                    path: object.location.to_string(),
+                    size: object.size,
+                    age: Duration::from_millis(age_ms as u64),
+                })
+            })
+            .collect::<Vec<_>>();
+        orphans.sort_unstable_by_key(|sst| sst.id);
+        let mut missing_ids = missing_ids.into_iter().collect::<Vec<_>>();
+        missing_ids.sort_unstable();
+
+        Ok(ConsistencyReport {
+            missing_ssts: missing_ids,
+            orphaned_ssts: orphans,
+        })
+    }
+
+    /// Delete the given ssts, ids still referenced by manifest, not found or
+    /// failed to delete are skipped. Returns ids deleted.
+    ///
+    /// Orphans younger than `min_age` are skipped too, since an in-flight
+    /// write or compaction may not have added its sst to manifest yet.
+    pub async fn delete(&self, ids: &[FileId]) -> Result<Vec<FileId>> {
+        let ids = ids.iter().collect::<HashSet<_>>();
+        let orphans = self.find().await?;
+        let mut to_deletes = Vec::new();
+        for orphan in orphans.into_iter().filter(|sst| ids.contains(&sst.id)) {
+            if orphan.age < self.min_age {
+                warn!(
+                    id = orphan.id,
+                    age = ?orphan.age,
+                    min_age = ?self.min_age,
+                    "Skip deleting young orphaned sst"
+                );
+                continue;
+            }
+            to_deletes.push(orphan.id);
+        }
+        let deleted = purge_ssts(
+            &self.store,
+            &self.sst_path_gen,
+            &self.event_notifier,
+            to_deletes,
+        )
+        .await;
+        info!(ids = ?deleted, "Orphaned ssts deleted");
+
+        Ok(deleted)
+    }
+
+    /// Report orphans every `interval`, they are never deleted automatically.
+    pub async fn run(&self, interval: Duration) {
+        loop {
+            sleep(interval).await;
+            match self.find().await {
+                Ok(orphans) if orphans.is_empty() => {}
+                Ok(orphans) => {
+                    let total_size: usize = orphans.iter().map(|sst| sst.size).sum();
+                    warn!(
+                        num = orphans.len(),
+                        total_size,
+                        first_10 = ?orphans.iter().take(10).collect::<Vec<_>>(),
+                        "Orphaned ssts found"
+                    );
+                }
+                Err(e) => error!("Find orphaned ssts failed, err:{e}"),
+            }
+        }
+    }
+}
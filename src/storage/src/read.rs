@@ -55,7 +55,9 @@ use parquet::arrow::async_reader::ParquetObjectReader;
 use crate::{
     compare_primitive_columns,
     config::UpdateMode,
-    operator::{BytesMergeOperator, LastValueOperator, MergeOperator, MergeOperatorRef},
+    operator::{
+        BytesMergeOperator, LastNValuesOperator, LastValueOperator, MergeOperator, MergeOperatorRef,
+    },
     sst::{SstFile, SstPathGenerator},
     types::{
         ObjectStoreRef, StorageSchema, BUILTIN_COLUMN_NUM, RESERVED_COLUMN_NAME, SEQ_COLUMN_NAME,
@@ -487,6 +489,7 @@ impl ParquetReader {
                 UpdateMode::Append => {
                     Arc::new(BytesMergeOperator::new(self.schema.value_idxes.clone()))
                 }
+                UpdateMode::MaxVersions(n) => Arc::new(LastNValuesOperator::new(n)),
             },
             keep_builtin,
         );
@@ -503,7 +506,7 @@ mod tests {
     use super::*;
     use crate::{
         arrow_schema,
-        operator::{BytesMergeOperator, LastValueOperator, MergeOperatorRef},
+        operator::{BytesMergeOperator, LastNValuesOperator, LastValueOperator, MergeOperatorRef},
         record_batch,
         sst::FileMeta,
         test_util::{check_stream, make_sendable_record_batches},
@@ -534,6 +537,22 @@ mod tests {
         ];
 
         test_merge_stream_inner(Arc::new(BytesMergeOperator::new(vec![1])), expected).await;
+
+        let expected = [
+            record_batch!(
+                ("pk1", UInt8, vec![11, 11, 12, 12]),
+                ("value", Binary, vec![b"1", b"2", b"3", b"4"])
+            )
+            .unwrap(),
+            record_batch!(
+                ("pk1", UInt8, vec![13, 13]),
+                ("value", Binary, vec![b"7", b"8"])
+            )
+            .unwrap(),
+            record_batch!(("pk1", UInt8, vec![14]), ("value", Binary, vec![b"9"])).unwrap(),
+        ];
+
+        test_merge_stream_inner(Arc::new(LastNValuesOperator::new(2)), expected).await;
     }
 
     async fn test_merge_stream_inner<I>(merge_op: MergeOperatorRef, expected: I)
@@ -0,0 +1,155 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Helpers for spawning tasks on storage runtimes.
+
+use std::{any::Any, fmt, future::Future, panic::AssertUnwindSafe};
+
+use futures::FutureExt;
+use tokio::{runtime::Runtime, task::JoinHandle};
+use tracing::error;
+
+use crate::{Error, Result};
+
+/// Spawn `future` on `runtime`, panics inside it are caught and returned as
+/// [`Error::Panic`] with `context`, instead of an opaque `JoinError`.
+pub fn spawn_catch_panic<F, C>(
+    runtime: &Runtime,
+    context: C,
+    future: F,
+) -> JoinHandle<Result<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+    C: fmt::Display + Send + 'static,
+{
+    runtime.spawn(catch_panic(context, future))
+}
+
+/// Poll `future` to completion, converting panic into [`Error::Panic`].
+pub async fn catch_panic<F, C>(context: C, future: F) -> Result<F::Output>
+where
+    F: Future,
+    C: fmt::Display,
+{
+    AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .map_err(|panic| {
+            let message = panic_message(panic.as_ref());
+            let context = context.to_string();
+            error!(context, message, "Task panicked");
+            Error::Panic { context, message }
+        })
+}
+
+/// Point-in-time load of a runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeStats {
+    pub num_workers: usize,
+    /// Tasks spawned but not finished yet, including both running and queued
+    /// ones.
+    pub num_alive_tasks: usize,
+}
+
+impl RuntimeStats {
+    pub fn new(runtime: &Runtime) -> Self {
+        let metrics = runtime.metrics();
+        Self {
+            num_workers: metrics.num_workers(),
+            num_alive_tasks: metrics.num_alive_tasks(),
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_spawn_catch_panic() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let handle = spawn_catch_panic(&rt, "ok task", async { 1 });
+            assert_eq!(1, handle.await.unwrap().unwrap());
+
+            let handle = spawn_catch_panic(&rt, "compaction, task_id:1", async {
+                panic!("boom, value:{}", 42);
+            });
+            let err = handle.await.unwrap().unwrap_err();
+            match err {
+                Error::Panic { context, message } => {
+                    assert_eq!("compaction, task_id:1", context);
+                    assert_eq!("boom, value:42", message);
+                }
+                e => panic!("unexpected err:{e}"),
+            }
+        });
+    }
+
+    #[test]
+    fn test_runtime_stats() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(3)
+            .enable_all()
+            .build()
+            .unwrap();
+        assert_eq!(
+            RuntimeStats {
+                num_workers: 3,
+                num_alive_tasks: 0
+            },
+            RuntimeStats::new(&rt)
+        );
+
+        let (tx, rx) = tokio::sync::watch::channel(false);
+        let handles = (0..10)
+            .map(|_| {
+                let mut rx = rx.clone();
+                rt.spawn(async move { rx.wait_for(|v| *v).await.map(|_| ()) })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(10, RuntimeStats::new(&rt).num_alive_tasks);
+
+        tx.send(true).unwrap();
+        rt.block_on(async {
+            for handle in handles {
+                handle.await.unwrap().unwrap();
+            }
+            // Finished tasks are released after join handles are notified.
+            for _ in 0..100 {
+                if RuntimeStats::new(&rt).num_alive_tasks == 0 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        assert_eq!(0, RuntimeStats::new(&rt).num_alive_tasks);
+    }
+}
@@ -24,9 +24,14 @@ use std::{
     time::SystemTime,
 };
 
+use anyhow::Context;
+use object_store::path::Path;
+use tracing::{error, trace};
+
 use crate::{
     ensure,
-    types::{TimeRange, Timestamp},
+    event::{EventNotifier, StorageEvent},
+    types::{ObjectStoreRef, TimeRange, Timestamp},
     Error,
 };
 
@@ -202,4 +207,50 @@ impl SstPathGenerator {
     pub fn generate(&self, id: FileId) -> String {
         format!("{}/{}/{}.sst", self.prefix, PREFIX_PATH, id)
     }
+
+    /// Directory containing all sst files.
+    pub fn data_dir(&self) -> String {
+        format!("{}/{}", self.prefix, PREFIX_PATH)
+    }
+
+    /// Parse file id from sst file name, returns `None` for non-sst files.
+    pub fn parse_id(file_name: &str) -> Option<FileId> {
+        file_name.strip_suffix(".sst")?.parse().ok()
+    }
+}
+
+/// Delete sst files from object store, and notify [`StorageEvent::SstPurged`]
+/// for those deleted. All ssts leaving object store should go through this.
+///
+/// Failures are logged and skipped, returns ids of ssts deleted successfully.
+pub async fn purge_ssts<I>(
+    store: &ObjectStoreRef,
+    sst_path_gen: &SstPathGenerator,
+    event_notifier: &EventNotifier,
+    ids: I,
+) -> Vec<FileId>
+where
+    I: IntoIterator<Item = FileId>,
+{
+    let deletes = ids.into_iter().map(|id| async move {
+        let path = Path::from(sst_path_gen.generate(id));
+        trace!(id, "Delete sst file");
+        store
+            .delete(&path)
+            .await
+            .with_context(|| format!("failed to delete file, path:{path}"))?;
+        Ok::<_, anyhow::Error>(id)
+    });
+    let mut purged = Vec::new();
+    for res in futures::future::join_all(deletes).await {
+        match res {
+            Ok(id) => purged.push(id),
+            Err(e) => error!("Failed to delete sst, err:{e}"),
+        }
+    }
+    if !purged.is_empty() {
+        event_notifier.notify(StorageEvent::SstPurged(purged.clone()));
+    }
+
+    purged
 }
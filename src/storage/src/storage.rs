@@ -15,7 +15,14 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::{sync::Arc, time::Duration, vec};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+    vec,
+};
 
 use anyhow::Context;
 use arrow::{array::RecordBatch, datatypes::SchemaRef};
@@ -42,17 +49,24 @@ use parquet::{
     format::SortingColumn,
     schema::types::ColumnPath,
 };
-use tokio::runtime::Runtime;
+use tokio::{runtime::Runtime, sync::broadcast::Receiver};
+use tracing::{debug, warn};
 
+pub use crate::compaction::Task as CompactionPlan;
 use crate::{
     compaction::CompactionScheduler,
     config::{StorageConfig, WriteConfig},
+    disk_cache::DiskCacheStore,
     ensure,
+    event::{EventNotifier, StorageEvent},
+    limit_store::{LimitStore, LimitStoreStats},
     manifest::{Manifest, ManifestRef},
+    orphan::{ConsistencyReport, OrphanDetector, OrphanedSst},
     read::ParquetReader,
-    sst::{FileMeta, SstFile, SstPathGenerator},
+    runtime::spawn_catch_panic,
+    sst::{FileId, FileMeta, SstFile, SstPathGenerator},
     types::{ObjectStoreRef, StorageSchema, TimeRange, WriteResult, SEQ_COLUMN_NAME},
-    Result,
+    Error, Result,
 };
 
 pub struct WriteRequest {
@@ -67,6 +81,8 @@ pub struct ScanRequest {
     pub predicate: Vec<Expr>,
     /// `None` means all columns.
     pub projections: Option<Vec<usize>>,
+    /// Skip the `max_scan_time_range` check, for trusted callers only.
+    pub ignore_max_time_range: bool,
 }
 
 #[derive(Default)]
@@ -84,6 +100,32 @@ pub trait TimeMergeStorage {
     async fn scan(&self, req: ScanRequest) -> Result<SendableRecordBatchStream>;
 
     async fn compact(&self, req: CompactRequest) -> Result<()>;
+
+    /// Returns what the next compaction would do, without executing it.
+    async fn compaction_plan(&self) -> Result<Option<CompactionPlan>>;
+
+    /// Pause or resume compaction, ssts accumulate while it's paused.
+    fn set_compaction_enabled(&self, enabled: bool);
+
+    fn is_compaction_enabled(&self) -> bool;
+
+    /// Ssts in object store but not referenced by manifest.
+    async fn orphaned_ssts(&self) -> Result<Vec<OrphanedSst>>;
+
+    /// Read-only check between manifest and object store.
+    async fn consistency_check(&self) -> Result<ConsistencyReport>;
+
+    async fn stats(&self) -> StorageStats;
+}
+
+/// Point-in-time state of a storage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Ssts referenced by manifest.
+    pub num_ssts: usize,
+    pub compaction_enabled: bool,
+    /// `None` when object store requests are not limited.
+    pub object_store_requests: Option<LimitStoreStats>,
 }
 
 pub type TimeMergeStorageRef = Arc<(dyn TimeMergeStorage + Send + Sync)>;
@@ -120,6 +162,50 @@ pub struct CloudObjectStorage {
     write_props: WriterProperties,
     sst_path_gen: Arc<SstPathGenerator>,
     compact_scheduler: CompactionScheduler,
+    soft_sst_num_threshold: usize,
+    hard_sst_num_threshold: Option<usize>,
+    // Ssts being written but not added to manifest yet.
+    pending_ssts: AtomicUsize,
+    // Only for stats, `store` is wrapped by it when set.
+    limit_store: Option<Arc<LimitStore>>,
+    over_soft_sst_limit: AtomicBool,
+    max_write_batch_size: Option<usize>,
+    split_oversized_batch: bool,
+    orphan_detector: Arc<OrphanDetector>,
+    max_scan_time_range: Option<Duration>,
+    event_notifier: EventNotifier,
+}
+
+/// Slots reserved for ssts being written, released on drop.
+struct PendingSsts<'a> {
+    counter: &'a AtomicUsize,
+    num: usize,
+    // Slots reserved by other writes when this reservation is made.
+    others: usize,
+}
+
+impl<'a> PendingSsts<'a> {
+    fn reserve(counter: &'a AtomicUsize, num: usize) -> Self {
+        let others = counter.fetch_add(num, Ordering::SeqCst);
+        Self {
+            counter,
+            num,
+            others,
+        }
+    }
+
+    fn release_one(&mut self) {
+        if self.num > 0 {
+            self.num -= 1;
+            self.counter.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for PendingSsts<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(self.num, Ordering::SeqCst);
+    }
 }
 
 /// It will organize the data in the following way:
@@ -145,6 +231,25 @@ impl CloudObjectStorage {
     ) -> Result<Self> {
         let schema =
             StorageSchema::try_new(arrow_schema, num_primary_keys, storage_opts.update_mode)?;
+        ensure!(
+            storage_opts.event_channel_size > 0,
+            "event channel size should large than 0"
+        );
+        // All reads and writes, including manifest and compaction, share the
+        // same limit.
+        let limit_store = storage_opts
+            .max_concurrent_object_store_requests
+            .map(|limit| LimitStore::try_new(store.clone(), limit).map(Arc::new))
+            .transpose()?;
+        let store: ObjectStoreRef = match &limit_store {
+            Some(limit_store) => limit_store.clone(),
+            None => store,
+        };
+        // Put in front of the limit, so cache hits don't wait for permits.
+        let store: ObjectStoreRef = match &storage_opts.sst_disk_cache {
+            Some(config) => Arc::new(DiskCacheStore::try_new(store, config)?),
+            None => store,
+        };
         let manifest = Manifest::try_new(
             path.clone(),
             store.clone(),
@@ -153,6 +258,12 @@ impl CloudObjectStorage {
         )
         .await?;
         let manifest = Arc::new(manifest);
+        let max_scan_time_range = storage_opts.max_scan_time_range.map(|v| v.0);
+        let max_write_batch_size = storage_opts
+            .write
+            .max_write_batch_size
+            .map(|v| v.0 as usize);
+        let split_oversized_batch = storage_opts.write.split_oversized_batch;
         let write_props = Self::build_write_props(storage_opts.write, num_primary_keys);
         let sst_path_gen = Arc::new(SstPathGenerator::new(path.clone()));
         let parquet_reader = Arc::new(ParquetReader::new(
@@ -160,6 +271,24 @@ impl CloudObjectStorage {
             schema.clone(),
             sst_path_gen.clone(),
         ));
+        let soft_sst_num_threshold = storage_opts.scheduler.soft_sst_num_threshold;
+        let hard_sst_num_threshold = storage_opts.scheduler.hard_sst_num_threshold;
+        let event_notifier = EventNotifier::new(storage_opts.event_channel_size);
+        let orphan_detector = Arc::new(OrphanDetector::new(
+            store.clone(),
+            manifest.clone(),
+            sst_path_gen.clone(),
+            event_notifier.clone(),
+            storage_opts.orphaned_sst_min_age.0,
+        ));
+        if let Some(interval) = storage_opts.orphaned_sst_check_interval {
+            let orphan_detector = orphan_detector.clone();
+            spawn_catch_panic(
+                &runtimes.manifest_compact_runtime,
+                "orphaned sst detector",
+                async move { orphan_detector.run(interval.0).await },
+            );
+        }
         let compact_scheduler = CompactionScheduler::new(
             runtimes.sst_compact_runtime.clone(),
             manifest.clone(),
@@ -170,7 +299,8 @@ impl CloudObjectStorage {
             parquet_reader.clone(),
             storage_opts.scheduler,
             write_props.clone(),
-        );
+            event_notifier.clone(),
+        )?;
         Ok(Self {
             path,
             schema,
@@ -182,9 +312,110 @@ impl CloudObjectStorage {
             write_props,
             sst_path_gen,
             compact_scheduler,
+            soft_sst_num_threshold,
+            hard_sst_num_threshold,
+            pending_ssts: AtomicUsize::new(0),
+            limit_store,
+            over_soft_sst_limit: AtomicBool::new(false),
+            max_write_batch_size,
+            split_oversized_batch,
+            event_notifier,
+            orphan_detector,
+            max_scan_time_range,
         })
     }
 
+    /// Delete orphaned ssts confirmed by caller, see
+    /// [`OrphanDetector::delete`].
+    pub async fn delete_orphaned_ssts(&self, ids: &[FileId]) -> Result<Vec<FileId>> {
+        self.orphan_detector.delete(ids).await
+    }
+
+    /// Subscribe to sst change events, see [`StorageEvent`].
+    pub fn subscribe_events(&self) -> Receiver<StorageEvent> {
+        self.event_notifier.subscribe()
+    }
+
+    /// Too many ssts hurt scan performance, so compaction is triggered as soon
+    /// as sst num exceeds the soft threshold, and writes are rejected when
+    /// their `num_new_ssts` would exceed the hard threshold until compaction
+    /// catches up.
+    ///
+    /// Slots for the new ssts are reserved before reading sst num, so
+    /// concurrent writes can't pass the hard limit together. The returned
+    /// reservation should be released once each sst is added to manifest.
+    async fn check_sst_num(&self, num_new_ssts: usize) -> Result<PendingSsts<'_>> {
+        let pending = PendingSsts::reserve(&self.pending_ssts, num_new_ssts);
+        let sst_num = self.manifest.num_ssts().await;
+        if sst_num > self.soft_sst_num_threshold {
+            let compaction_enabled = self.compact_scheduler.is_enabled();
+            // Warn only when the soft limit is crossed, instead of on every
+            // write until compaction catches up.
+            if !self.over_soft_sst_limit.swap(true, Ordering::Relaxed) {
+                warn!(
+                    sst_num,
+                    soft_limit = self.soft_sst_num_threshold,
+                    compaction_enabled,
+                    "Too many ssts"
+                );
+            }
+            if compaction_enabled {
+                debug!(sst_num, "Too many ssts, trigger compaction");
+                if let Err(e) = self.compact_scheduler.trigger_compaction() {
+                    debug!("Trigger compaction failed, err:{e}");
+                }
+            }
+        } else {
+            self.over_soft_sst_limit.store(false, Ordering::Relaxed);
+        }
+        if let Some(hard_limit) = self.hard_sst_num_threshold {
+            // Ssts reserved by others may be added to manifest during the
+            // check, so they may be counted twice, which is conservative.
+            let others = pending.others;
+            ensure!(
+                sst_num + others + num_new_ssts <= hard_limit,
+                "Too many ssts, value:{sst_num}, pending:{others}, new:{num_new_ssts}, hard_limit:{hard_limit}"
+            );
+        }
+
+        Ok(pending)
+    }
+
+    /// Oversized batch is rejected, or split into smaller ones when
+    /// `split_oversized_batch` is enabled. Each split is written to its own
+    /// sst, so a failed write may leave preceding splits persisted.
+    ///
+    /// Each split also gets its own sequence, so duplicated keys within the
+    /// batch are deduplicated by split order: rows in a later split overwrite
+    /// rows in an earlier one, unlike an unsplit batch.
+    fn split_or_reject_batch(&self, batch: RecordBatch) -> Result<Vec<RecordBatch>> {
+        let Some(limit) = self.max_write_batch_size else {
+            return Ok(vec![batch]);
+        };
+        let size = batch.get_array_memory_size();
+        if size <= limit {
+            return Ok(vec![batch]);
+        }
+        if !self.split_oversized_batch {
+            return Err(Error::WriteBatchTooLarge { size, limit });
+        }
+
+        let num_rows = batch.num_rows();
+        let rows_per_split = (num_rows * limit / size).max(1);
+        let batches = (0..num_rows)
+            .step_by(rows_per_split)
+            .map(|offset| batch.slice(offset, rows_per_split.min(num_rows - offset)))
+            .collect::<Vec<_>>();
+        debug!(
+            size,
+            limit,
+            num_splits = batches.len(),
+            "Split oversized write batch"
+        );
+
+        Ok(batches)
+    }
+
     async fn write_batch(&self, batch: RecordBatch) -> Result<WriteResult> {
         let file_id = SstFile::allocate_id();
         let file_path = self.sst_path_gen.generate(file_id);
@@ -314,24 +545,44 @@ impl TimeMergeStorage for CloudObjectStorage {
             );
         }
 
-        let num_rows = req.batch.num_rows();
-        let WriteResult {
-            id: file_id,
-            seq,
-            size: file_size,
-        } = self.write_batch(req.batch).await?;
-        let file_meta = FileMeta {
-            max_sequence: seq,
-            num_rows: num_rows as u32,
-            size: file_size as u32,
-            time_range: req.time_range,
-        };
-        self.manifest.add_file(file_id, file_meta).await?;
+        let batches = self.split_or_reject_batch(req.batch)?;
+        let mut pending = self.check_sst_num(batches.len()).await?;
+
+        for batch in batches {
+            let num_rows = batch.num_rows();
+            let WriteResult {
+                id: file_id,
+                seq,
+                size: file_size,
+            } = self.write_batch(batch).await?;
+            let file_meta = FileMeta {
+                max_sequence: seq,
+                num_rows: num_rows as u32,
+                size: file_size as u32,
+                time_range: req.time_range.clone(),
+            };
+            self.manifest.add_file(file_id, file_meta.clone()).await?;
+            pending.release_one();
+            self.event_notifier
+                .notify(StorageEvent::SstCreated(SstFile::new(file_id, file_meta)));
+        }
 
         Ok(())
     }
 
     async fn scan(&self, mut req: ScanRequest) -> Result<SendableRecordBatchStream> {
+        if let Some(limit) = self
+            .max_scan_time_range
+            .filter(|_| !req.ignore_max_time_range)
+        {
+            let range = req.range.end.0.saturating_sub(req.range.start.0);
+            ensure!(
+                range <= limit.as_millis() as i64,
+                "Scan time range too large, value:{:?}, limit:{limit:?}",
+                &req.range
+            );
+        }
+
         let total_ssts = self.manifest.find_ssts(&req.range).await;
         if total_ssts.is_empty() {
             return Ok(Box::pin(EmptyRecordBatchStream::new(
@@ -371,22 +622,110 @@ impl TimeMergeStorage for CloudObjectStorage {
     async fn compact(&self, _req: CompactRequest) -> Result<()> {
         self.compact_scheduler.trigger_compaction()
     }
+
+    async fn compaction_plan(&self) -> Result<Option<CompactionPlan>> {
+        Ok(self.compact_scheduler.compaction_plan().await)
+    }
+
+    fn set_compaction_enabled(&self, enabled: bool) {
+        self.compact_scheduler.set_enabled(enabled)
+    }
+
+    fn is_compaction_enabled(&self) -> bool {
+        self.compact_scheduler.is_enabled()
+    }
+
+    async fn orphaned_ssts(&self) -> Result<Vec<OrphanedSst>> {
+        self.orphan_detector.find().await
+    }
+
+    async fn consistency_check(&self) -> Result<ConsistencyReport> {
+        self.orphan_detector.check().await
+    }
+
+    async fn stats(&self) -> StorageStats {
+        StorageStats {
+            num_ssts: self.manifest.num_ssts().await,
+            compaction_enabled: self.compact_scheduler.is_enabled(),
+            object_store_requests: self.limit_store.as_ref().map(|v| v.stats()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::atomic::Ordering,
+    };
+
+    use common::{ReadableDuration, ReadableSize};
     use datafusion::logical_expr::{col, lit};
-    use object_store::local::LocalFileSystem;
+    use futures::TryStreamExt;
+    use object_store::{local::LocalFileSystem, ObjectStore};
+    use parquet::{
+        basic::{Compression, ZstdLevel},
+        file::reader::{FileReader, SerializedFileReader},
+    };
+    use temp_dir::TempDir;
     use test_log::test;
 
     use super::*;
-    use crate::{arrow_schema, record_batch, test_util::check_stream, types::Timestamp};
+    use crate::{
+        arrow_schema,
+        config::{ColumnOptions, DiskCacheConfig, ParquetCompression},
+        record_batch,
+        test_util::{check_stream, MockObjectStore},
+        types::{Timestamp, RESERVED_COLUMN_NAME},
+    };
 
     fn build_runtimes() -> StorageRuntimes {
         let rt = Arc::new(Runtime::new().unwrap());
         StorageRuntimes::new(rt.clone(), rt)
     }
 
+    /// Storage with schema `(pk1 UInt8, value Int64)` and 2h segment, the
+    /// returned dir must outlive the storage.
+    async fn new_test_storage(
+        config: StorageConfig,
+        runtimes: StorageRuntimes,
+    ) -> (TempDir, CloudObjectStorage) {
+        new_test_storage_with_store(Arc::new(LocalFileSystem::new()), config, runtimes).await
+    }
+
+    async fn new_test_storage_with_store(
+        store: ObjectStoreRef,
+        config: StorageConfig,
+        runtimes: StorageRuntimes,
+    ) -> (TempDir, CloudObjectStorage) {
+        let root_dir = TempDir::new().unwrap();
+        let storage = CloudObjectStorage::try_new(
+            root_dir.path().to_string_lossy().to_string(),
+            Duration::from_hours(2),
+            store,
+            arrow_schema!(("pk1", UInt8), ("value", Int64)),
+            1, // num_primary_keys
+            config,
+            runtimes,
+        )
+        .await
+        .unwrap();
+
+        (root_dir, storage)
+    }
+
+    /// Write row `(1, value)` to storage created by [`new_test_storage`].
+    async fn write_one_row(storage: &CloudObjectStorage, value: i64) -> Result<()> {
+        let batch = record_batch!(("pk1", UInt8, vec![1]), ("value", Int64, vec![value])).unwrap();
+        storage
+            .write(WriteRequest {
+                batch,
+                time_range: (1..10).into(),
+                enable_check: true,
+            })
+            .await
+    }
+
     #[test(test)]
     fn test_storage_write_and_scan() {
         let schema = arrow_schema!(("pk1", UInt8), ("pk2", UInt8), ("value", Int64));
@@ -441,6 +780,7 @@ mod tests {
                     range: TimeRange::new(Timestamp(0), Timestamp::MAX),
                     predicate: vec![],
                     projections: None,
+                    ignore_max_time_range: false,
                 })
                 .await
                 .unwrap();
@@ -468,6 +808,7 @@ mod tests {
                     range: TimeRange::new(Timestamp(0), Timestamp::MAX),
                     predicate: vec![expr],
                     projections: None,
+                    ignore_max_time_range: false,
                 })
                 .await
                 .unwrap();
@@ -489,9 +830,451 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_storage_reject_write_over_sst_limit() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let mut config = StorageConfig::default();
+            config.scheduler.hard_sst_num_threshold = Some(1);
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+
+            write_one_row(&storage, 1).await.unwrap();
+            let err = write_one_row(&storage, 2).await.unwrap_err();
+            assert!(err.to_string().contains("Too many ssts"));
+            assert_eq!(1, storage.manifest.num_ssts().await);
+        });
+    }
+
+    #[test]
+    fn test_storage_reject_concurrent_write_over_sst_limit() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let mut config = StorageConfig::default();
+            config.scheduler.compaction_enabled = false;
+            config.scheduler.hard_sst_num_threshold = Some(3);
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+
+            let writes = (0..10).map(|value| write_one_row(&storage, value));
+            let results = futures::future::join_all(writes).await;
+            assert_eq!(3, results.iter().filter(|res| res.is_ok()).count());
+            assert_eq!(3, storage.stats().await.num_ssts);
+            assert_eq!(0, storage.pending_ssts.load(Ordering::SeqCst));
+        });
+    }
+
+    #[test]
+    fn test_storage_trigger_compaction_over_soft_limit() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let mut config = StorageConfig::default();
+            config.scheduler.schedule_interval = ReadableDuration::hours(1);
+            config.scheduler.soft_sst_num_threshold = 1;
+            config.scheduler.input_sst_min_num = 2;
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+
+            // Third write sees 2 ssts and triggers compaction.
+            for value in 0..3 {
+                write_one_row(&storage, value).await.unwrap();
+            }
+            let mut num_ssts = 0;
+            for _ in 0..100 {
+                num_ssts = storage.manifest.num_ssts().await;
+                if num_ssts < 3 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            assert!(num_ssts < 3, "num_ssts:{num_ssts}");
+        });
+    }
+
+    #[test]
+    fn test_storage_write_events() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let (_dir, storage) = new_test_storage(StorageConfig::default(), runtimes).await;
+            let mut events = storage.subscribe_events();
+
+            write_one_row(&storage, 1).await.unwrap();
+
+            let ssts = storage.manifest.all_ssts().await;
+            assert_eq!(1, ssts.len());
+            let event = events.recv().await.unwrap();
+            assert_eq!(StorageEvent::SstCreated(ssts[0].clone()), event);
+        });
+    }
+
+    #[test]
+    fn test_storage_reject_zero_event_channel_size() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let root_dir = TempDir::new().unwrap();
+            let config = StorageConfig {
+                event_channel_size: 0,
+                ..Default::default()
+            };
+            let res = CloudObjectStorage::try_new(
+                root_dir.path().to_string_lossy().to_string(),
+                Duration::from_hours(2),
+                Arc::new(LocalFileSystem::new()),
+                arrow_schema!(("pk1", UInt8), ("value", Int64)),
+                1, // num_primary_keys
+                config,
+                runtimes,
+            )
+            .await;
+            let err = res.err().unwrap();
+            assert!(err.to_string().contains("event channel size"), "{err}");
+        });
+    }
+
+    #[test]
+    fn test_storage_disable_compaction() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let mut config = StorageConfig::default();
+            config.scheduler.compaction_enabled = false;
+            // Resumed compaction should start without waiting for it.
+            config.scheduler.schedule_interval = ReadableDuration::hours(1);
+            config.scheduler.input_sst_min_num = 2;
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+
+            for value in 0..3 {
+                write_one_row(&storage, value).await.unwrap();
+            }
+            storage.compact(CompactRequest::default()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(3, storage.manifest.num_ssts().await);
+
+            let input_ids = storage
+                .manifest
+                .all_ssts()
+                .await
+                .iter()
+                .map(|sst| sst.id())
+                .collect::<HashSet<_>>();
+            let mut events = storage.subscribe_events();
+            assert!(!storage.stats().await.compaction_enabled);
+            storage.set_compaction_enabled(true);
+            assert!(storage.stats().await.compaction_enabled);
+            let mut num_ssts = 0;
+            for _ in 0..100 {
+                num_ssts = storage.manifest.num_ssts().await;
+                if num_ssts == 1 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            assert_eq!(1, num_ssts);
+            match events.recv().await.unwrap() {
+                StorageEvent::CompactionCompleted {
+                    inputs, expireds, ..
+                } => {
+                    assert_eq!(input_ids, inputs.into_iter().collect());
+                    assert!(expireds.is_empty());
+                }
+                event => panic!("unexpected event:{event:?}"),
+            }
+        });
+    }
+
+    #[test]
+    fn test_storage_write_batch_size_limit() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            async fn write(storage: &CloudObjectStorage, num_rows: usize) -> Result<()> {
+                let batch = record_batch!(
+                    ("pk1", UInt8, vec![1; num_rows]),
+                    ("value", Int64, (0..num_rows as i64).collect::<Vec<_>>())
+                )
+                .unwrap();
+                storage
+                    .write(WriteRequest {
+                        batch,
+                        time_range: (1..10).into(),
+                        enable_check: true,
+                    })
+                    .await
+            }
+
+            let mut config = StorageConfig::default();
+            config.write.max_write_batch_size = Some(ReadableSize(1024));
+            let (_dir, storage) = new_test_storage(config.clone(), runtimes.clone()).await;
+            write(&storage, 10).await.unwrap();
+            let err = write(&storage, 1000).await.unwrap_err();
+            assert!(
+                matches!(err, Error::WriteBatchTooLarge { limit: 1024, .. }),
+                "{err}"
+            );
+            assert_eq!(1, storage.manifest.num_ssts().await);
+
+            config.write.split_oversized_batch = true;
+            let (_dir, storage) = new_test_storage(config.clone(), runtimes.clone()).await;
+            write(&storage, 1000).await.unwrap();
+            let ssts = storage.manifest.all_ssts().await;
+            assert!(ssts.len() > 1);
+            let total_rows: u32 = ssts.iter().map(|sst| sst.meta().num_rows).sum();
+            assert_eq!(1000, total_rows);
+
+            // All splits are counted against the hard limit.
+            config.scheduler.hard_sst_num_threshold = Some(2);
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+            let err = write(&storage, 1000).await.unwrap_err();
+            assert!(err.to_string().contains("Too many ssts"), "{err}");
+            assert_eq!(0, storage.manifest.num_ssts().await);
+        });
+    }
+
+    #[test]
+    fn test_storage_write_compression() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let mut config = StorageConfig::default();
+            config.write.compression = ParquetCompression::Zstd;
+            config.write.column_options = Some(HashMap::from([(
+                "value".to_string(),
+                ColumnOptions {
+                    compression: Some(ParquetCompression::Uncompressed),
+                    ..Default::default()
+                },
+            )]));
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+
+            write_one_row(&storage, 1).await.unwrap();
+
+            let ssts = storage.manifest.all_ssts().await;
+            let file = std::fs::File::open(storage.sst_path_gen.generate(ssts[0].id())).unwrap();
+            let reader = SerializedFileReader::new(file).unwrap();
+            let row_group = reader.metadata().row_group(0);
+            let compressions = row_group
+                .columns()
+                .iter()
+                .map(|c| (c.column_path().string(), c.compression()))
+                .collect::<HashMap<_, _>>();
+            assert_eq!(
+                HashMap::from([
+                    ("pk1".to_string(), Compression::ZSTD(ZstdLevel::default())),
+                    ("value".to_string(), Compression::UNCOMPRESSED),
+                    (
+                        SEQ_COLUMN_NAME.to_string(),
+                        Compression::ZSTD(ZstdLevel::default())
+                    ),
+                    (
+                        RESERVED_COLUMN_NAME.to_string(),
+                        Compression::ZSTD(ZstdLevel::default())
+                    ),
+                ]),
+                compressions
+            );
+        });
+    }
+
+    #[test]
+    fn test_storage_orphaned_ssts() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let config = StorageConfig {
+                orphaned_sst_min_age: ReadableDuration::secs(0),
+                ..Default::default()
+            };
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+            let store = storage.store.clone();
+
+            write_one_row(&storage, 1).await.unwrap();
+            assert!(storage.orphaned_ssts().await.unwrap().is_empty());
+
+            let orphan_id = SstFile::allocate_id();
+            let orphan_path = Path::from(storage.sst_path_gen.generate(orphan_id));
+            store.put(&orphan_path, "orphan".into()).await.unwrap();
+            let orphans = storage.orphaned_ssts().await.unwrap();
+            assert_eq!(1, orphans.len());
+            assert_eq!(orphan_id, orphans[0].id);
+            assert_eq!(6, orphans[0].size);
+            // Detection never deletes.
+            assert!(store.head(&orphan_path).await.is_ok());
+
+            // Live ssts are never deleted even if requested.
+            let live_id = storage.manifest.all_ssts().await[0].id();
+            let mut events = storage.subscribe_events();
+            let deleted = storage
+                .delete_orphaned_ssts(&[live_id, orphan_id])
+                .await
+                .unwrap();
+            assert_eq!(vec![orphan_id], deleted);
+            match events.recv().await.unwrap() {
+                StorageEvent::SstPurged(ids) => assert_eq!(vec![orphan_id], ids),
+                event => panic!("unexpected event: {event:?}"),
+            }
+            assert!(store.head(&orphan_path).await.is_err());
+            assert_eq!(1, storage.manifest.num_ssts().await);
+            assert!(storage.orphaned_ssts().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_storage_keep_young_orphaned_ssts() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let (_dir, storage) = new_test_storage(StorageConfig::default(), runtimes).await;
+            let store = storage.store.clone();
+
+            // Unreferenced sst just uploaded, may belong to an in-flight write.
+            let orphan_id = SstFile::allocate_id();
+            let orphan_path = Path::from(storage.sst_path_gen.generate(orphan_id));
+            store.put(&orphan_path, "orphan".into()).await.unwrap();
+            let deleted = storage.delete_orphaned_ssts(&[orphan_id]).await.unwrap();
+            assert!(deleted.is_empty());
+            assert!(store.head(&orphan_path).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_storage_max_scan_time_range() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let config = StorageConfig {
+                max_scan_time_range: Some(ReadableDuration::hours(1)),
+                ..Default::default()
+            };
+            let (_dir, storage) = new_test_storage(config, runtimes).await;
+
+            let scan = |end, ignore_max_time_range| {
+                storage.scan(ScanRequest {
+                    range: TimeRange::new(Timestamp(0), Timestamp(end)),
+                    predicate: vec![],
+                    projections: None,
+                    ignore_max_time_range,
+                })
+            };
+            assert!(scan(3_600_000, false).await.is_ok());
+            let err = scan(3_600_001, false).await.err().unwrap();
+            assert!(err.to_string().contains("Scan time range too large"));
+            assert!(scan(i64::MAX, false).await.is_err());
+            assert!(scan(3_600_001, true).await.is_ok());
+            assert!(scan(i64::MAX, true).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_storage_consistency_check() {
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let (_dir, storage) = new_test_storage(StorageConfig::default(), runtimes).await;
+            let store = storage.store.clone();
+
+            for value in 0..2 {
+                write_one_row(&storage, value).await.unwrap();
+            }
+            assert!(storage.consistency_check().await.unwrap().is_consistent());
+
+            let missing_id = storage.manifest.all_ssts().await[0].id();
+            let missing_path = Path::from(storage.sst_path_gen.generate(missing_id));
+            store.delete(&missing_path).await.unwrap();
+            let orphan_id = SstFile::allocate_id();
+            let orphan_path = Path::from(storage.sst_path_gen.generate(orphan_id));
+            store.put(&orphan_path, "orphan".into()).await.unwrap();
+
+            let report = storage.consistency_check().await.unwrap();
+            assert!(!report.is_consistent());
+            assert_eq!(vec![missing_id], report.missing_ssts);
+            assert_eq!(
+                vec![orphan_id],
+                report
+                    .orphaned_ssts
+                    .iter()
+                    .map(|sst| sst.id)
+                    .collect::<Vec<_>>()
+            );
+            // Check is read-only.
+            assert_eq!(2, storage.manifest.num_ssts().await);
+            assert!(store.head(&orphan_path).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_storage_object_store_concurrency_limit() {
+        let store = Arc::new(MockObjectStore::new().with_get_delay(Duration::from_millis(20)));
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let config = StorageConfig {
+                max_concurrent_object_store_requests: Some(2),
+                ..Default::default()
+            };
+            let (_dir, storage) =
+                new_test_storage_with_store(store.clone(), config, runtimes).await;
+
+            for value in 0..10 {
+                write_one_row(&storage, value).await.unwrap();
+            }
+            let scans = (0..3).map(|_| async {
+                let stream = storage
+                    .scan(ScanRequest {
+                        range: TimeRange::new(Timestamp(0), Timestamp(10)),
+                        predicate: vec![],
+                        projections: None,
+                        ignore_max_time_range: false,
+                    })
+                    .await
+                    .unwrap();
+                stream.try_collect::<Vec<_>>().await.unwrap()
+            });
+            let results = futures::future::join_all(scans).await;
+            let expected =
+                record_batch!(("pk1", UInt8, vec![1]), ("value", Int64, vec![9])).unwrap();
+            for batches in results {
+                assert_eq!(vec![expected.clone()], batches);
+            }
+            assert_eq!(2, store.max_in_flight_gets.load(Ordering::SeqCst));
+            assert_eq!(
+                Some(LimitStoreStats::default()),
+                storage.stats().await.object_store_requests
+            );
+        });
+    }
+
+    #[test]
+    fn test_storage_sst_disk_cache() {
+        let store = Arc::new(MockObjectStore::new());
+        let cache_dir = temp_dir::TempDir::new().unwrap();
+        let runtimes = build_runtimes();
+        runtimes.sst_compact_runtime.clone().block_on(async move {
+            let config = StorageConfig {
+                sst_disk_cache: Some(DiskCacheConfig {
+                    dir: cache_dir.path().to_string_lossy().to_string(),
+                    capacity: ReadableSize::mb(1),
+                }),
+                ..Default::default()
+            };
+            let (_dir, storage) =
+                new_test_storage_with_store(store.clone(), config, runtimes).await;
+            write_one_row(&storage, 1).await.unwrap();
+
+            let expected =
+                record_batch!(("pk1", UInt8, vec![1]), ("value", Int64, vec![1])).unwrap();
+            let mut num_gets = Vec::new();
+            for _ in 0..2 {
+                let stream = storage
+                    .scan(ScanRequest {
+                        range: TimeRange::new(Timestamp(0), Timestamp(10)),
+                        predicate: vec![],
+                        projections: None,
+                        ignore_max_time_range: false,
+                    })
+                    .await
+                    .unwrap();
+                let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+                assert_eq!(vec![expected.clone()], batches);
+                num_gets.push(store.num_gets.load(Ordering::SeqCst));
+            }
+            assert!(num_gets[0] > 0);
+            // Second scan is served from disk.
+            assert_eq!(num_gets[0], num_gets[1]);
+        });
+    }
+
     #[test]
     fn test_storage_sort_batch() {
-        let schema = arrow_schema!(("a", UInt8), ("b", UInt8), ("c", UInt8), ("c", UInt8));
+        let schema = arrow_schema!(("a", UInt8), ("b", UInt8), ("c", UInt8), ("d", UInt8));
         let root_dir = temp_dir::TempDir::new().unwrap();
         let store = Arc::new(LocalFileSystem::new());
         let runtimes = build_runtimes();
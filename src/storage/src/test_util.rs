@@ -17,16 +17,24 @@
 
 use std::{
     collections::VecDeque,
+    fmt,
     pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
     task::{Context, Poll},
+    time::Duration,
 };
 
 use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use async_trait::async_trait;
 use datafusion::{
     error::Result as DfResult,
     execution::{RecordBatchStream, SendableRecordBatchStream},
 };
-use futures::{Stream, StreamExt};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use object_store::{
+    local::LocalFileSystem, path::Path, GetOptions, GetResult, ListResult, MultipartUpload,
+    ObjectMeta, ObjectStore, PutMode, PutMultipartOpts, PutOptions, PutPayload, PutResult,
+};
 
 #[macro_export]
 macro_rules! arrow_schema {
@@ -164,6 +172,130 @@ where
     assert!(iter.next().is_none());
 }
 
+/// Object store wrapping [`LocalFileSystem`] which can inject put conflicts
+/// and slow gets, and records the number of gets and the max number of
+/// concurrent gets.
+#[derive(Debug)]
+pub struct MockObjectStore {
+    inner: LocalFileSystem,
+    put_conflicts: AtomicUsize,
+    write_on_conflict: bool,
+    get_delay: Option<Duration>,
+    in_flight_gets: AtomicUsize,
+    pub max_in_flight_gets: AtomicUsize,
+    pub num_gets: AtomicUsize,
+}
+
+impl MockObjectStore {
+    pub fn new() -> Self {
+        Self {
+            inner: LocalFileSystem::new(),
+            put_conflicts: AtomicUsize::new(0),
+            write_on_conflict: false,
+            get_delay: None,
+            in_flight_gets: AtomicUsize::new(0),
+            max_in_flight_gets: AtomicUsize::new(0),
+            num_gets: AtomicUsize::new(0),
+        }
+    }
+
+    /// Report the first `conflicts` create puts as already existing.
+    pub fn with_put_conflicts(mut self, conflicts: usize) -> Self {
+        self.put_conflicts = AtomicUsize::new(conflicts);
+        self
+    }
+
+    /// Write the object before reporting a conflict, like a retried put whose
+    /// first attempt succeeded.
+    pub fn with_write_on_conflict(mut self) -> Self {
+        self.write_on_conflict = true;
+        self
+    }
+
+    /// Delay every get by `delay`.
+    pub fn with_get_delay(mut self, delay: Duration) -> Self {
+        self.get_delay = Some(delay);
+        self
+    }
+}
+
+impl fmt::Display for MockObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MockObjectStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MockObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        if matches!(opts.mode, PutMode::Create)
+            && self
+                .put_conflicts
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| v.checked_sub(1))
+                .is_ok()
+        {
+            if self.write_on_conflict {
+                self.inner.put_opts(location, payload, opts).await?;
+            }
+            return Err(object_store::Error::AlreadyExists {
+                path: location.to_string(),
+                source: "mock conflict".into(),
+            });
+        }
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.num_gets.fetch_add(1, Ordering::SeqCst);
+        let in_flight = self.in_flight_gets.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight_gets
+            .fetch_max(in_flight, Ordering::SeqCst);
+        if let Some(delay) = self.get_delay {
+            tokio::time::sleep(delay).await;
+        }
+        let res = self.inner.get_opts(location, options).await;
+        self.in_flight_gets.fetch_sub(1, Ordering::SeqCst);
+        res
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
 mod tests {
     use futures::StreamExt;
 
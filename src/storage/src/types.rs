@@ -16,6 +16,7 @@
 // under the License.
 
 use std::{
+    collections::HashSet,
     fmt,
     ops::{Add, Deref, Range},
     sync::Arc,
@@ -163,12 +164,23 @@ impl StorageSchema {
         update_mode: UpdateMode,
     ) -> Result<Self> {
         ensure!(num_primary_keys > 0, "num_primary_keys should large than 0");
+        if let UpdateMode::MaxVersions(n) = update_mode {
+            ensure!(n > 0, "max versions should large than 0");
+        }
 
         let fields = arrow_schema.fields();
         ensure!(
             !fields.iter().any(Self::is_builtin_field),
             "schema should not use builtin columns name"
         );
+        let mut names = HashSet::with_capacity(fields.len());
+        for field in fields {
+            ensure!(
+                names.insert(field.name()),
+                "schema should not contain duplicate column, name:{}",
+                field.name()
+            );
+        }
 
         let value_idxes = (num_primary_keys..arrow_schema.fields.len()).collect::<Vec<_>>();
         ensure!(!value_idxes.is_empty(), "no value column found");
@@ -271,6 +283,12 @@ mod tests {
         // No value column exists
         assert!(StorageSchema::try_new(arrow_schema, 3, UpdateMode::Append).is_err());
 
+        let duplicated = arrow_schema!(("pk1", UInt8), ("value", Int64), ("value", Int64));
+        let err = StorageSchema::try_new(duplicated, 1, UpdateMode::Append).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("schema should not contain duplicate column, name:value"));
+
         let batch = record_batch!(
             ("pk1", UInt8, vec![11, 11, 9, 10]),
             ("pk2", UInt8, vec![100, 99, 1, 2]),